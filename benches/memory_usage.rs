@@ -0,0 +1,91 @@
+//! Measures the "Memory Usage After Removing Silence" column of the "Improve Memory Usage"
+//! table in the README for the current code.
+//!
+//! Run with `cargo bench --bench memory_usage`. The size of the generated WAV file defaults to
+//! 300 MB and can be changed with the `MEMORY_BENCH_MB` environment variable.
+//! Memory figures are read from `/proc/self/status` and are only available on Linux.
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+use solid_meme::app::SoundApp;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SAMPLE_RATE: u32 = 44100;
+const CHANNELS: u16 = 2;
+
+fn main() {
+    let size_mb: usize = std::env::var("MEMORY_BENCH_MB")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(300);
+    let path = std::env::temp_dir().join("solid_meme_memory_bench.wav");
+    write_test_file(&path, size_mb);
+
+    println!("## Processing a {} MB WAV File", size_mb);
+    println!();
+    println!("| Step                    | Resident Memory | File-backed | Peak Memory | Time     |");
+    println!("|-------------------------|----------------:|------------:|------------:|---------:|");
+    report("Start", Duration::ZERO);
+
+    let mut app = SoundApp::new();
+    let start = Instant::now();
+    app.load_path(&path);
+    report("After load", start.elapsed());
+
+    let start = Instant::now();
+    app.remove_all_silence_background();
+    while app.is_processing {
+        app.update_processing();
+        thread::sleep(Duration::from_millis(10));
+    }
+    report("After removing silence", start.elapsed());
+
+    let _ = std::fs::remove_file(&path);
+}
+
+/// Writes alternating one second tones and one second silences, like speech with pauses.
+fn write_test_file(path: &Path, size_mb: usize) {
+    let spec = WavSpec {
+        channels: CHANNELS,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+    let mut writer = WavWriter::create(path, spec).expect("Failed to create test file");
+    let total_frames = size_mb * 1024 * 1024 / (CHANNELS as usize * 2);
+    for i in 0..total_frames {
+        let second = i / SAMPLE_RATE as usize;
+        let sample = if second % 2 == 0 {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            ((t * 440.0 * std::f32::consts::TAU).sin() * 16000.0) as i16
+        } else {
+            0
+        };
+        for _ in 0..CHANNELS {
+            writer.write_sample(sample).unwrap();
+        }
+    }
+    writer.finalize().unwrap();
+}
+
+fn report(step: &str, elapsed: Duration) {
+    let format = |kb: Option<usize>| match kb {
+        Some(kb) => format!("{:.1} MB", kb as f64 / 1024.0),
+        None => String::from("n/a"),
+    };
+    println!(
+        "| {:<23} | {:>15} | {:>11} | {:>11} | {:>6.2} s |",
+        step,
+        format(read_status_kb("VmRSS:")),
+        format(read_status_kb("RssFile:")),
+        format(read_status_kb("VmHWM:")),
+        elapsed.as_secs_f64()
+    );
+}
+
+fn read_status_kb(key: &str) -> Option<usize> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with(key))?;
+    line[key.len()..].trim().trim_end_matches("kB").trim().parse().ok()
+}
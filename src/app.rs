@@ -1,25 +1,283 @@
-use cpal::traits::StreamTrait;
-use crate::audio::{play_samples, PlaybackSource, WaveformData};
-use hound::{WavReader, WavWriter};
+use crate::audio::{detect_silence_parallel, non_silent_regions, output_device_names, PlaybackSource, WaveformData};
+use crate::envelope::Envelope;
+use crate::stream::remove_silence_streaming;
+use crate::subtitle::{SubtitleFormat, Subtitles};
+use crate::timeline::TimelineMap;
+use crate::job::{CancelToken, Progress};
+use crate::peaks::PeakPyramid;
+use crate::playback::{Transport, TransportState};
+use crate::resample::resample;
+use crate::spectrogram::{compute_spectrogram, overview_columns, DisplayMode, SpectrogramSettings, MAX_COLUMNS};
+use crate::wav::{read_wav, SampleBuffer};
+use eframe::egui::{self, ColorImage, TextureHandle, TextureOptions};
+use hound::{WavSpec, WavWriter};
 use rfd::FileDialog;
-use std::sync::{Arc, mpsc::{self, Receiver}};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::BufWriter;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, mpsc::{self, Receiver, TryRecvError}};
 use std::thread;
+use std::time::{Duration, Instant};
+
+/// Decoded samples of a file loaded in the background, or `None` if loading was cancelled.
+pub type LoadResult = Result<Option<(PathBuf, WavSpec, SampleBuffer)>, String>;
+
+/// Silence detected by a live preview, tagged with the parameters it was detected with.
+pub type PreviewResult = ((f32, usize), Vec<(usize, usize)>);
+
+/// Spectrogram image tagged with the settings it was computed with.
+pub type SpectrogramResult = (SpectrogramSettings, ColorImage);
+
+/// Spectrogram of part of the file, tagged with its settings and the frames it covers.
+pub type SpectrogramDetailResult = (SpectrogramSettings, Range<usize>, ColorImage);
+
+/// How much coarser than one column per pixel a spectrogram may get before the visible range
+/// is computed again at a higher resolution.
+const SPECTROGRAM_DETAIL_TOLERANCE: f64 = 1.5;
+
+/// How long the silence parameters must stay unchanged before the live preview re-detects.
+const PREVIEW_DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// How long the silence parameters must stay unchanged before a running job is restarted.
+/// Restarting scans the whole file on every core, so it waits a little longer than the preview.
+const RESTART_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// How the channels of a multi-channel file are drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelView {
+    /// One lane per channel.
+    Lanes,
+    /// A single lane with the channels averaged.
+    Mixdown,
+}
+
+/// Most pixels one frame may be stretched across when zoomed in.
+const MAX_PIXELS_PER_FRAME: f64 = 32.0;
+
+/// Most pixels the zoomed content may span. An `f64` offset still resolves a small fraction
+/// of a pixel at this size, so panning and pointer-anchored zoom stay smooth in any file.
+const MAX_CONTENT_PIXELS: f64 = (1u64 << 40) as f64;
+
+/// Zoom and scroll position of one waveform view. Zoom 1 fits the whole file in the view, and
+/// `offset` is the scroll position in pixels of the zoomed content, so pixel `x` shows sample
+/// `(x + offset) * total_samples / width / zoom`. Both are `f64`: a long file zoomed in spans
+/// more pixels than an `f32` can count one by one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WaveformView {
+    pub zoom: f64,
+    pub offset: f64,
+}
+
+impl Default for WaveformView {
+    fn default() -> Self {
+        Self { zoom: 1.0, offset: 0.0 }
+    }
+}
+
+impl WaveformView {
+    /// Zoom at which one frame spans `MAX_PIXELS_PER_FRAME` pixels, or the content reaches
+    /// `MAX_CONTENT_PIXELS`.
+    pub fn max_zoom(total_samples: f64, channels: f64, width: f32) -> f64 {
+        let width = width as f64;
+        (total_samples / channels * MAX_PIXELS_PER_FRAME / width)
+            .min(MAX_CONTENT_PIXELS / width)
+            .max(1.0)
+    }
+
+    pub fn samples_per_pixel(&self, total_samples: f64, width: f32) -> f64 {
+        total_samples / width as f64 / self.zoom
+    }
+
+    /// The sample shown at pixel `x` from the view's left edge, fractional between samples.
+    pub fn sample_at(&self, x: f32, total_samples: f64, width: f32) -> f64 {
+        (x as f64 + self.offset) * self.samples_per_pixel(total_samples, width)
+    }
+
+    /// Pixel from the view's left edge where `sample` is drawn; outside `0..width` when the
+    /// sample is scrolled out of view.
+    pub fn x_of(&self, sample: f64, total_samples: f64, width: f32) -> f32 {
+        (sample / self.samples_per_pixel(total_samples, width) - self.offset) as f32
+    }
+
+    /// Zooms by `factor` while keeping the content under `anchor_x` (relative to the view's
+    /// left edge) in place.
+    pub fn zoom_at(&mut self, factor: f64, anchor_x: f32, total_samples: f64, channels: f64, width: f32) {
+        let zoom = (self.zoom * factor).clamp(1.0, Self::max_zoom(total_samples, channels, width));
+        let anchor_x = anchor_x as f64;
+        self.offset = (anchor_x + self.offset) * zoom / self.zoom - anchor_x;
+        self.zoom = zoom;
+        self.clamp_offset(width);
+    }
+
+    /// Zooms so samples `start..end` fill the view.
+    pub fn zoom_to_range(&mut self, start: usize, end: usize, total_samples: f64, channels: f64, width: f32) {
+        if end <= start {
+            return;
+        }
+        self.zoom = (total_samples / (end - start) as f64).clamp(1.0, Self::max_zoom(total_samples, channels, width));
+        self.offset = start as f64 / total_samples * width as f64 * self.zoom;
+        self.clamp_offset(width);
+    }
+
+    /// Scrolls so `sample` is in the middle of the view.
+    pub fn center_on(&mut self, sample: f64, total_samples: f64, width: f32) {
+        self.offset = sample / self.samples_per_pixel(total_samples, width) - width as f64 / 2.0;
+        self.clamp_offset(width);
+    }
+
+    /// Scrolls by `dx` pixels, e.g. while dragging.
+    pub fn pan(&mut self, dx: f32, width: f32) {
+        self.offset += dx as f64;
+        self.clamp_offset(width);
+    }
+
+    /// Keeps the view within the file: the content spans `width * zoom` pixels.
+    pub fn clamp_offset(&mut self, width: f32) {
+        let width = width as f64;
+        self.offset = self.offset.clamp(0.0, (width * self.zoom - width).max(0.0));
+    }
+}
+
+/// Takes a background worker's result, if it has arrived. The receiver is cleared once the
+/// worker is done, including when it stopped without a result, e.g. because it was cancelled.
+fn poll<T>(rx: &mut Option<Receiver<T>>) -> Option<T> {
+    let result = rx.as_ref()?.try_recv();
+    match result {
+        Ok(value) => {
+            *rx = None;
+            Some(value)
+        }
+        Err(TryRecvError::Disconnected) => {
+            *rx = None;
+            None
+        }
+        Err(TryRecvError::Empty) => None,
+    }
+}
+
+/// Converts samples to `rate`, if one is given, with the playback resampler.
+/// Returns `None` if cancelled.
+fn for_export<'a, F: FnMut(f32)>(
+    spec: WavSpec,
+    samples: &'a [i16],
+    rate: Option<u32>,
+    cancel: &CancelToken,
+    on_progress: F,
+) -> Option<(WavSpec, Cow<'a, [i16]>)> {
+    match rate {
+        Some(rate) if rate != spec.sample_rate => {
+            let converted = resample(samples, spec.channels as usize, spec.sample_rate, rate, cancel, on_progress)?;
+            Some((WavSpec { sample_rate: rate, ..spec }, Cow::Owned(converted)))
+        }
+        _ => Some((spec, Cow::Borrowed(samples))),
+    }
+}
+
+/// True if both paths name the same existing file.
+fn same_file(a: &Path, b: &Path) -> bool {
+    matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+}
+
+fn write_wav(path: &Path, spec: WavSpec, samples: &[i16]) -> hound::Result<()> {
+    let mut writer = WavWriter::create(path, spec)?;
+    for &sample in samples {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize()
+}
+
+/// What the transport plays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransportSource {
+    Original,
+    Processed,
+    /// The selected range of whichever waveform it was made on.
+    Selection,
+}
+
+/// The kind of background job behind `is_processing`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProcessingJob {
+    Detect,
+    Remove,
+    Stream,
+    Export,
+}
 
 pub struct SoundApp {
     pub raw_waveform: WaveformData,
     pub processed_waveform: WaveformData,
     pub spec: Option<hound::WavSpec>,
+    pub file_path: Option<PathBuf>,
     pub file_loaded: bool,
-    pub zoom: f32,
-    pub offset: f32,
+    pub is_loading: bool,
+    pub loading_progress: Progress,
+    pub load_rx: Option<Receiver<LoadResult>>,
+    pub load_cancel: CancelToken,
+    pub raw_view: WaveformView,
+    pub processed_view: WaveformView,
+    pub link_views: bool,
+    pub view_leader: PlaybackSource,
+    pub selection: Option<(PlaybackSource, usize, usize)>,
+    pub show_rms: bool,
+    pub db_scale: bool,
+    pub channel_view: ChannelView,
+    pub cache_peaks: bool,
+    pub raw_peaks_rx: Option<Receiver<PeakPyramid>>,
+    pub processed_peaks_rx: Option<Receiver<PeakPyramid>>,
+    pub peaks_cancel: CancelToken,
+    pub display_mode: DisplayMode,
+    pub spectrogram_settings: SpectrogramSettings,
+    pub spectrogram: Option<(SpectrogramSettings, TextureHandle)>,
+    pub spectrogram_rx: Option<Receiver<SpectrogramResult>>,
+    pub spectrogram_requested: Option<SpectrogramSettings>,
+    pub spectrogram_progress: Progress,
+    pub spectrogram_cancel: CancelToken,
+    /// Spectrogram of the frames around the view, while zoomed in further than the whole-file
+    /// image resolves.
+    pub spectrogram_detail: Option<(SpectrogramSettings, Range<usize>, TextureHandle)>,
+    pub spectrogram_detail_rx: Option<Receiver<SpectrogramDetailResult>>,
+    pub spectrogram_detail_requested: Option<(SpectrogramSettings, Range<usize>)>,
+    pub spectrogram_detail_cancel: CancelToken,
     pub processed_ready: bool,
+    pub timeline: TimelineMap,
     pub silence_threshold: f32,
     pub min_silence_len: usize,
+    pub split_pattern: String,
+    pub split_min_len: usize,
+    pub export_sample_rate: Option<u32>,
+    pub live_preview: bool,
+    pub envelope: Option<Arc<Envelope>>,
+    pub envelope_rx: Option<Receiver<Envelope>>,
+    pub envelope_cancel: CancelToken,
+    pub preview_params: (f32, usize),
+    pub preview_changed_at: Option<Instant>,
+    pub preview_rx: Option<Receiver<PreviewResult>>,
+    pub preview_cancel: CancelToken,
     pub is_processing: bool,
-    pub processing_progress: f32,
-    pub progress_rx: Option<Receiver<f32>>,
+    pub processing_job: Option<ProcessingJob>,
+    pub processing_params: (f32, usize),
+    /// Parameters last seen by `restart_if_params_changed`, and when they changed.
+    pub restart_params: (f32, usize),
+    pub restart_changed_at: Option<Instant>,
+    pub processing_cancel: CancelToken,
+    pub processing_progress: Progress,
     pub result_rx: Option<Receiver<(Vec<(usize, usize)>, Option<Vec<i16>>)>>,
-    pub stop_rx: Option<Receiver<PlaybackSource>>,
+    pub stream_rx: Option<Receiver<Result<String, String>>>,
+    pub export_rx: Option<Receiver<Result<String, String>>>,
+    pub status_message: String,
+    pub transport: Transport,
+    pub transport_source: TransportSource,
+    pub output_devices: Option<Vec<String>>,
+    pub output_device: Option<String>,
+}
+
+impl Default for SoundApp {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SoundApp {
@@ -28,36 +286,344 @@ impl SoundApp {
             raw_waveform: WaveformData::new(),
             processed_waveform: WaveformData::new(),
             spec: None,
+            file_path: None,
             file_loaded: false,
-            zoom: 1.0,
-            offset: 0.0,
+            is_loading: false,
+            loading_progress: Progress::new(),
+            load_rx: None,
+            load_cancel: CancelToken::new(),
+            raw_view: WaveformView::default(),
+            processed_view: WaveformView::default(),
+            link_views: true,
+            view_leader: PlaybackSource::Raw,
+            selection: None,
+            show_rms: false,
+            db_scale: false,
+            channel_view: ChannelView::Lanes,
+            cache_peaks: false,
+            raw_peaks_rx: None,
+            processed_peaks_rx: None,
+            peaks_cancel: CancelToken::new(),
+            display_mode: DisplayMode::Waveform,
+            spectrogram_settings: SpectrogramSettings::default(),
+            spectrogram: None,
+            spectrogram_rx: None,
+            spectrogram_requested: None,
+            spectrogram_progress: Progress::new(),
+            spectrogram_cancel: CancelToken::new(),
+            spectrogram_detail: None,
+            spectrogram_detail_rx: None,
+            spectrogram_detail_requested: None,
+            spectrogram_detail_cancel: CancelToken::new(),
             processed_ready: false,
+            timeline: TimelineMap::default(),
             silence_threshold: 0.01,
             min_silence_len: 1000,
+            split_pattern: String::from("segment_{n}"),
+            split_min_len: 0,
+            export_sample_rate: None,
+            live_preview: true,
+            envelope: None,
+            envelope_rx: None,
+            envelope_cancel: CancelToken::new(),
+            preview_params: (0.0, 0),
+            preview_changed_at: None,
+            preview_rx: None,
+            preview_cancel: CancelToken::new(),
             is_processing: false,
-            processing_progress: 0.0,
-            progress_rx: None,
+            processing_job: None,
+            processing_params: (0.0, 0),
+            restart_params: (0.0, 0),
+            restart_changed_at: None,
+            processing_cancel: CancelToken::new(),
+            processing_progress: Progress::new(),
             result_rx: None,
-            stop_rx: None,
+            stream_rx: None,
+            export_rx: None,
+            status_message: String::new(),
+            transport: Transport::new(),
+            transport_source: TransportSource::Original,
+            output_devices: None,
+            output_device: None,
         }
     }
 
+    /// Picks a file and loads it in the background. The current file stays usable until
+    /// the new one has finished loading.
     pub fn load_file(&mut self) {
-        if let Some(path) = FileDialog::new().add_filter("WAV", &["wav"]).pick_file() {
-            if let Ok(mut reader) = WavReader::open(&path) {
-                let spec = reader.spec();
-                let raw_samples: Vec<i16> = reader.samples().map(|s| s.unwrap()).collect();
-                println!("Loaded raw samples count: {}", raw_samples.len());
-                self.raw_waveform = WaveformData::from_samples(raw_samples.clone());
-                self.processed_waveform = WaveformData::from_samples(raw_samples);
-                self.spec = Some(spec);
-                self.file_loaded = true;
-                self.zoom = 1.0;
-                self.offset = 0.0;
-                self.processed_ready = false;
-                self.is_processing = false;
-                self.processing_progress = 0.0;
+        if self.is_loading {
+            return;
+        }
+        let Some(path) = FileDialog::new().add_filter("WAV", &["wav"]).pick_file() else {
+            return;
+        };
+        self.is_loading = true;
+        self.loading_progress = Progress::new();
+
+        let (load_tx, load_rx) = mpsc::channel();
+        let cancel = CancelToken::new();
+        let progress = self.loading_progress.clone();
+        self.load_rx = Some(load_rx);
+        self.load_cancel = cancel.clone();
+
+        thread::spawn(move || {
+            let result = match read_wav(&path, &cancel, |fraction| progress.set(fraction)) {
+                Ok(loaded) => Ok(loaded.map(|(spec, samples)| (path, spec, samples))),
+                Err(err) => Err(format!("Failed to load {:?}: {}", path, err)),
+            };
+            let _ = load_tx.send(result);
+        });
+    }
+
+    pub fn cancel_loading(&mut self) {
+        self.load_cancel.cancel();
+        self.is_loading = false;
+        self.load_rx = None;
+    }
+
+    /// Loads a file on the calling thread.
+    pub fn load_path(&mut self, path: &Path) {
+        match read_wav(path, &CancelToken::new(), |_| {}) {
+            Ok(Some((spec, samples))) => self.set_loaded(path.to_path_buf(), spec, samples),
+            Ok(None) => {}
+            Err(err) => self.status_message = format!("Failed to load {:?}: {}", path, err),
+        }
+    }
+
+    fn set_loaded(&mut self, path: PathBuf, spec: WavSpec, raw_samples: SampleBuffer) {
+        println!("Loaded raw samples count: {}", raw_samples.len());
+        self.timeline = TimelineMap::identity(raw_samples.len());
+        self.raw_waveform = WaveformData::from_shared(Arc::new(raw_samples));
+        // Nothing has been removed yet, so the processed waveform shares the original buffer
+        self.processed_waveform = WaveformData::from_shared(Arc::clone(&self.raw_waveform.samples_raw));
+        self.spec = Some(spec);
+        self.file_path = Some(path);
+        self.file_loaded = true;
+        self.raw_view = WaveformView::default();
+        self.processed_view = WaveformView::default();
+        self.view_leader = PlaybackSource::Raw;
+        self.selection = None;
+        self.transport.unload();
+        self.processed_ready = false;
+        // Detection and removal of the previous file no longer apply. Streaming and exports
+        // hold their own input and finish writing their files, reporting as usual
+        if matches!(self.processing_job, Some(ProcessingJob::Detect | ProcessingJob::Remove)) {
+            self.cancel_processing();
+        }
+        self.compute_envelope_background();
+        self.peaks_cancel.cancel();
+        self.peaks_cancel = CancelToken::new();
+        self.processed_peaks_rx = None;
+        self.build_peaks_background(PlaybackSource::Raw);
+        self.spectrogram_cancel.cancel();
+        self.spectrogram = None;
+        self.spectrogram_rx = None;
+        self.spectrogram_requested = None;
+        self.spectrogram_detail_cancel.cancel();
+        self.spectrogram_detail = None;
+        self.spectrogram_detail_rx = None;
+        self.spectrogram_detail_requested = None;
+    }
+
+    /// Keeps the spectrogram of the original waveform in step with the display settings.
+    /// The STFT runs off the UI thread; the finished image is uploaded as a texture here.
+    pub fn update_spectrogram(&mut self, ctx: &egui::Context) {
+        if let Some((settings, image)) = poll(&mut self.spectrogram_rx) {
+            let texture = ctx.load_texture("spectrogram", image, TextureOptions::LINEAR);
+            self.spectrogram = Some((settings, texture));
+        }
+        if let Some((settings, frames, image)) = poll(&mut self.spectrogram_detail_rx) {
+            let texture = ctx.load_texture("spectrogram_detail", image, TextureOptions::LINEAR);
+            self.spectrogram_detail = Some((settings, frames, texture));
+        }
+
+        if self.display_mode == DisplayMode::Waveform || !self.file_loaded {
+            return;
+        }
+        let settings = self.spectrogram_settings;
+        let current = self.spectrogram.as_ref().map(|(s, _)| *s);
+        if current == Some(settings) || self.spectrogram_requested == Some(settings) {
+            return;
+        }
+
+        self.spectrogram_cancel.cancel();
+        let (spectrogram_tx, spectrogram_rx) = mpsc::channel();
+        let cancel = CancelToken::new();
+        let progress = Progress::new();
+        self.spectrogram_rx = Some(spectrogram_rx);
+        self.spectrogram_cancel = cancel.clone();
+        self.spectrogram_progress = progress.clone();
+        self.spectrogram_requested = Some(settings);
+
+        let samples = Arc::clone(&self.raw_waveform.samples_raw);
+        let channels = self.spec.map_or(1, |spec| spec.channels as usize);
+        thread::spawn(move || {
+            let total_frames = samples.len() / channels;
+            let columns = overview_columns(total_frames, settings.fft_size);
+            let image = compute_spectrogram(&samples, channels, settings, 0..total_frames, columns, &cancel, |f| {
+                progress.set(f)
+            });
+            if let Some(image) = image {
+                let _ = spectrogram_tx.send((settings, image));
             }
+        });
+    }
+
+    /// Computes the spectrogram of the `visible` frames, drawn `width` pixels wide, when the
+    /// whole-file image has too few columns there to show detail. The range is padded on both
+    /// sides so small pans don't need a new image.
+    pub fn update_spectrogram_detail(&mut self, visible: Range<usize>, width: f32) {
+        let Some(spec) = self.spec.filter(|_| self.file_loaded) else {
+            return;
+        };
+        let channels = spec.channels as usize;
+        let total_frames = self.raw_waveform.samples_raw.len() / channels;
+        let settings = self.spectrogram_settings;
+        if visible.is_empty() || total_frames == 0 {
+            return;
+        }
+
+        // Frames per column to aim for: one column per pixel, within the image size limit
+        let target_hop = (visible.len() as f64 / width.max(1.0) as f64)
+            .max(visible.len() as f64 / MAX_COLUMNS as f64)
+            .max(1.0);
+        let overview_hop = total_frames as f64 / overview_columns(total_frames, settings.fft_size) as f64;
+        if overview_hop <= target_hop * SPECTROGRAM_DETAIL_TOLERANCE {
+            return;
+        }
+        let sharp_enough = |(detail_settings, frames): (&SpectrogramSettings, &Range<usize>)| {
+            let hop = frames.len() as f64 / frames.len().min(MAX_COLUMNS) as f64;
+            *detail_settings == settings
+                && frames.start <= visible.start
+                && frames.end >= visible.end
+                && hop <= target_hop * SPECTROGRAM_DETAIL_TOLERANCE
+        };
+        let current = self.spectrogram_detail.as_ref().map(|(s, frames, _)| (s, frames));
+        let requested = self.spectrogram_detail_requested.as_ref().map(|(s, frames)| (s, frames));
+        if current.is_some_and(sharp_enough) || requested.is_some_and(sharp_enough) {
+            return;
+        }
+
+        let len = ((MAX_COLUMNS as f64 * target_hop) as usize).min(2 * visible.len()).max(visible.len());
+        let start = visible.start.saturating_sub((len - visible.len()) / 2);
+        let end = (start + len).min(total_frames);
+        let frames = end.saturating_sub(len)..end;
+
+        self.spectrogram_detail_cancel.cancel();
+        let (detail_tx, detail_rx) = mpsc::channel();
+        let cancel = CancelToken::new();
+        self.spectrogram_detail_rx = Some(detail_rx);
+        self.spectrogram_detail_cancel = cancel.clone();
+        self.spectrogram_detail_requested = Some((settings, frames.clone()));
+
+        let samples = Arc::clone(&self.raw_waveform.samples_raw);
+        thread::spawn(move || {
+            let image = compute_spectrogram(&samples, channels, settings, frames.clone(), MAX_COLUMNS, &cancel, |_| {});
+            if let Some(image) = image {
+                let _ = detail_tx.send((settings, frames, image));
+            }
+        });
+    }
+
+    /// Builds the peak pyramid used to draw a waveform, off the UI thread. The original
+    /// waveform's peaks are read from, or saved to, a sidecar file when `cache_peaks` is set.
+    fn build_peaks_background(&mut self, source: PlaybackSource) {
+        let (peaks_tx, peaks_rx) = mpsc::channel();
+        let cancel = self.peaks_cancel.clone();
+        let (samples, cache_path) = match source {
+            PlaybackSource::Raw => {
+                self.raw_peaks_rx = Some(peaks_rx);
+                let cache_path = self.file_path.clone().filter(|_| self.cache_peaks);
+                (Arc::clone(&self.raw_waveform.samples_raw), cache_path)
+            }
+            PlaybackSource::Processed => {
+                self.processed_peaks_rx = Some(peaks_rx);
+                (Arc::clone(&self.processed_waveform.samples_raw), None)
+            }
+        };
+        let channels = self.spec.map_or(1, |spec| spec.channels as usize);
+
+        thread::spawn(move || {
+            if let Some(path) = &cache_path {
+                if let Ok(peaks) = PeakPyramid::load(path, samples.len() / channels, channels) {
+                    let _ = peaks_tx.send(peaks);
+                    return;
+                }
+            }
+            let Some(peaks) = PeakPyramid::build(&samples, channels, &cancel) else {
+                return;
+            };
+            if let Some(path) = &cache_path {
+                if let Err(err) = peaks.save(path) {
+                    eprintln!("Failed to save peak cache for {:?}: {}", path, err);
+                }
+            }
+            let _ = peaks_tx.send(peaks);
+        });
+    }
+
+    /// Precomputes the amplitude envelope used by the live preview.
+    fn compute_envelope_background(&mut self) {
+        self.envelope_cancel.cancel();
+        self.preview_cancel.cancel();
+        self.envelope = None;
+        self.preview_rx = None;
+
+        let (envelope_tx, envelope_rx) = mpsc::channel();
+        let cancel = CancelToken::new();
+        self.envelope_rx = Some(envelope_rx);
+        self.envelope_cancel = cancel.clone();
+
+        let samples = Arc::clone(&self.raw_waveform.samples_raw);
+        let channels = self.spec.map_or(1, |spec| spec.channels as usize);
+        thread::spawn(move || {
+            if let Some(envelope) = Envelope::compute(samples, channels, &cancel) {
+                let _ = envelope_tx.send(envelope);
+            }
+        });
+    }
+
+    /// Re-detects silence from the envelope once the sliders have settled, so the overlay
+    /// follows the threshold and minimum length without clicking "Detect Silence".
+    fn update_live_preview(&mut self) {
+        if let Some(envelope) = poll(&mut self.envelope_rx) {
+            self.envelope = Some(Arc::new(envelope));
+            // Forces a preview of the newly loaded file on the next check
+            self.preview_params = (f32::NAN, 0);
+        }
+        if let Some((params, silence_segments)) = poll(&mut self.preview_rx) {
+            if params == (self.silence_threshold, self.min_silence_len) && !self.processed_ready {
+                self.raw_waveform.silence_segments = silence_segments;
+            }
+        }
+
+        // Once silence has been removed, the overlay shows the segments that were actually cut,
+        // which the processed waveform, the timeline and "Export Split" all rely on
+        if !self.live_preview || self.processed_ready {
+            self.preview_changed_at = None;
+            return;
+        }
+        let params = (self.silence_threshold, self.min_silence_len);
+        if params != self.preview_params {
+            self.preview_params = params;
+            self.preview_changed_at = Some(Instant::now());
+        }
+        let settled = self.preview_changed_at.is_some_and(|t| t.elapsed() >= PREVIEW_DEBOUNCE);
+        if let (true, Some(envelope), Some(spec)) = (settled, &self.envelope, self.spec) {
+            self.preview_changed_at = None;
+            self.preview_cancel.cancel();
+
+            let (preview_tx, preview_rx) = mpsc::channel();
+            let cancel = CancelToken::new();
+            self.preview_rx = Some(preview_rx);
+            self.preview_cancel = cancel.clone();
+
+            let envelope = Arc::clone(envelope);
+            thread::spawn(move || {
+                if let Some(silence_segments) = envelope.detect(spec, params.0, params.1, &cancel) {
+                    let _ = preview_tx.send((params, silence_segments));
+                }
+            });
         }
     }
 
@@ -65,61 +631,23 @@ impl SoundApp {
         if self.is_processing || !self.file_loaded || self.spec.is_none() {
             return;
         }
-        self.is_processing = true;
-        self.processing_progress = 0.0;
+        let (cancel, progress) = self.start_processing(ProcessingJob::Detect);
 
-        let (progress_tx, progress_rx) = mpsc::channel();
         let (result_tx, result_rx) = mpsc::channel();
-        self.progress_rx = Some(progress_rx);
         self.result_rx = Some(result_rx);
 
         let spec = self.spec.unwrap();
-        let channels = spec.channels as usize;
-        let sample_rate = spec.sample_rate as usize;
         let samples = Arc::clone(&self.raw_waveform.samples_raw); // shared data
-        let total_samples = samples.len();
         let threshold = self.silence_threshold;
         let min_len = self.min_silence_len;
 
         thread::spawn(move || {
-            let mut silence_segments = Vec::new();
-            let mut silence_count = 0;
-            let mut silence_start = 0;
-
-            for i in (0..total_samples).step_by(channels) {
-                let mut frame_amplitude = 0.0;
-                for ch in 0..channels {
-                    if i + ch < total_samples {
-                        let sample = samples[i + ch] as f32;
-                        frame_amplitude += sample.abs() / i16::MAX as f32;
-                    }
-                }
-                frame_amplitude /= channels as f32;
-
-                if frame_amplitude < threshold {
-                    if silence_count == 0 {
-                        silence_start = i;
-                    }
-                    silence_count += 1;
-                } else if silence_count > 0 {
-                    let min_samples = min_len * sample_rate / 1000;
-                    if silence_count >= min_samples {
-                        silence_segments.push((silence_start, i));
-                    }
-                    silence_count = 0;
-                }
-
-                let progress = i as f32 / total_samples as f32;
-                if (progress * 100.0) as usize % 1 == 0 {
-                    let _ = progress_tx.send(progress);
-                }
-            }
-
-            if silence_count >= min_len * sample_rate / 1000 {
-                silence_segments.push((silence_start, total_samples));
+            let on_progress = |fraction| progress.set(fraction);
+            if let Some(silence_segments) =
+                detect_silence_parallel(&samples, spec, threshold, min_len, &cancel, on_progress)
+            {
+                let _ = result_tx.send((silence_segments, None));
             }
-
-            let _ = result_tx.send((silence_segments, None));
         });
     }
 
@@ -127,220 +655,587 @@ impl SoundApp {
         if self.is_processing || !self.file_loaded || self.spec.is_none() {
             return;
         }
-        self.is_processing = true;
-        self.processing_progress = 0.0;
+        let (cancel, progress) = self.start_processing(ProcessingJob::Remove);
 
-        let (progress_tx, progress_rx) = mpsc::channel();
         let (result_tx, result_rx) = mpsc::channel();
-        self.progress_rx = Some(progress_rx);
         self.result_rx = Some(result_rx);
 
         let spec = self.spec.unwrap();
-        let channels = spec.channels as usize;
-        let sample_rate = spec.sample_rate as usize;
         let samples = Arc::clone(&self.raw_waveform.samples_raw);
-        let total_samples = samples.len();
         let threshold = self.silence_threshold;
         let min_len = self.min_silence_len;
 
         thread::spawn(move || {
-            let mut silence_segments = Vec::new();
-            let mut silence_count = 0;
-            let mut silence_start = 0;
-            let mut result_samples = Vec::new();
-            let mut last_end = 0;
-
-            for i in (0..total_samples).step_by(channels) {
-                let mut frame_amplitude = 0.0;
-                for ch in 0..channels {
-                    if i + ch < total_samples {
-                        let sample = samples[i + ch] as f32;
-                        frame_amplitude += sample.abs() / i16::MAX as f32;
-                    }
-                }
-                frame_amplitude /= channels as f32;
+            let on_progress = |fraction| progress.set(fraction);
+            let Some(silence_segments) =
+                detect_silence_parallel(&samples, spec, threshold, min_len, &cancel, on_progress)
+            else {
+                return;
+            };
 
-                if frame_amplitude < threshold {
-                    if silence_count == 0 {
-                        silence_start = i;
-                    }
-                    silence_count += 1;
-                } else if silence_count > 0 {
-                    let min_samples = min_len * sample_rate / 1000;
-                    if silence_count >= min_samples {
-                        silence_segments.push((silence_start, i));
-                        for j in (last_end..silence_start).step_by(channels) {
-                            for ch in 0..channels {
-                                if j + ch < total_samples {
-                                    result_samples.push(samples[j + ch]);
-                                }
-                            }
-                        }
-                        last_end = i;
-                    }
-                    silence_count = 0;
-                }
-
-                let progress = i as f32 / total_samples as f32;
-                if (progress * 100.0) as usize % 1 == 0 {
-                    let _ = progress_tx.send(progress);
-                }
+            // Trailing silence is removed as well, so the output matches the detected segments
+            let regions = non_silent_regions(&silence_segments, samples.len());
+            let mut result_samples = Vec::with_capacity(regions.iter().map(|&(s, e)| e - s).sum());
+            for (start, end) in regions {
+                result_samples.extend_from_slice(&samples[start..end]);
             }
 
-            if silence_count >= min_len * sample_rate / 1000 {
-                silence_segments.push((silence_start, total_samples));
-            }
+            let _ = result_tx.send((silence_segments, Some(result_samples)));
+        });
+    }
 
-            for i in (last_end..total_samples).step_by(channels) {
-                for ch in 0..channels {
-                    if i + ch < total_samples {
-                        result_samples.push(samples[i + ch]);
-                    }
-                }
-            }
+    /// Removes silence from a file on disk straight into a new file, without loading it.
+    /// Intended for recordings too large to fit in memory.
+    pub fn process_file_streaming(&mut self) {
+        if self.is_processing {
+            return;
+        }
+        let Some(input) = FileDialog::new().add_filter("WAV", &["wav"]).pick_file() else {
+            return;
+        };
+        let Some(output) = FileDialog::new()
+            .add_filter("WAV", &["wav"])
+            .set_file_name("output.wav")
+            .save_file()
+        else {
+            return;
+        };
+        if self.overwrites_mapped_file(&output) || same_file(&input, &output) {
+            self.status_message = format!("Cannot write to {:?} while reading from it; choose another file", output);
+            return;
+        }
+        let (cancel, progress) = self.start_processing(ProcessingJob::Stream);
 
-            let _ = result_tx.send((silence_segments, Some(result_samples)));
+        let (stream_tx, stream_rx) = mpsc::channel();
+        self.stream_rx = Some(stream_rx);
+
+        let threshold = self.silence_threshold;
+        let min_len = self.min_silence_len;
+
+        thread::spawn(move || {
+            let result = remove_silence_streaming(&input, &output, threshold, min_len, &cancel, |fraction| {
+                progress.set(fraction);
+            });
+            let message = match result {
+                Ok(summary) => Ok(format!(
+                    "Removed {} silence segments, saved {} of {} samples to {:?}",
+                    summary.silence_segments.len(),
+                    summary.samples_written,
+                    summary.samples_read,
+                    output
+                )),
+                Err(err) => Err(format!("Failed to process {:?}: {}", input, err)),
+            };
+            let _ = stream_tx.send(message);
         });
     }
 
-    pub fn update_processing(&mut self) {
-        if let Some(ref rx) = self.progress_rx {
-            while let Ok(progress) = rx.try_recv() {
-                self.processing_progress = progress;
+    /// True if writing `path` would truncate the file the original waveform is memory-mapped
+    /// from, which would crash the next read of its samples.
+    fn overwrites_mapped_file(&self, path: &Path) -> bool {
+        matches!(*self.raw_waveform.samples_raw, SampleBuffer::Mapped(_))
+            && self.file_path.as_deref().is_some_and(|loaded| same_file(loaded, path))
+    }
+
+    fn start_processing(&mut self, job: ProcessingJob) -> (CancelToken, Progress) {
+        self.is_processing = true;
+        self.processing_job = Some(job);
+        self.processing_params = (self.silence_threshold, self.min_silence_len);
+        self.processing_progress = Progress::new();
+        self.processing_cancel = CancelToken::new();
+        (self.processing_cancel.clone(), self.processing_progress.clone())
+    }
+
+    pub fn cancel_processing(&mut self) {
+        self.processing_cancel.cancel();
+        self.is_processing = false;
+        self.processing_job = None;
+        self.result_rx = None;
+        self.stream_rx = None;
+        self.export_rx = None;
+    }
+
+    /// Restarts a running detection or removal when the silence parameters change,
+    /// instead of letting it finish with stale values.
+    fn restart_if_params_changed(&mut self) {
+        let params = (self.silence_threshold, self.min_silence_len);
+        let restartable = matches!(self.processing_job, Some(ProcessingJob::Detect | ProcessingJob::Remove));
+        if !restartable || params == self.processing_params {
+            self.restart_params = params;
+            self.restart_changed_at = None;
+            return;
+        }
+        // Wait for a dragged slider to settle instead of restarting the scan every frame
+        if params != self.restart_params {
+            self.restart_params = params;
+            self.restart_changed_at = Some(Instant::now());
+        }
+        if self.restart_changed_at.map_or(true, |t| t.elapsed() < RESTART_DEBOUNCE) {
+            return;
+        }
+        self.restart_changed_at = None;
+        match self.processing_job {
+            Some(ProcessingJob::Detect) => {
+                self.cancel_processing();
+                self.detect_silence_background();
+            }
+            Some(ProcessingJob::Remove) => {
+                self.cancel_processing();
+                self.remove_all_silence_background();
             }
+            Some(ProcessingJob::Stream | ProcessingJob::Export) | None => {}
         }
-        if let Some(ref rx) = self.result_rx {
-            if let Ok((silence_segments, result_samples)) = rx.try_recv() {
-                self.raw_waveform.silence_segments = silence_segments;
-                if let Some(samples) = result_samples {
-                    self.processed_waveform.samples_raw = Arc::new(samples);
-                    self.processed_ready = true;
+    }
+
+    /// True while a background job or playback is running, so the UI has to keep redrawing
+    /// to show progress and notice results.
+    pub fn has_pending_work(&self) -> bool {
+        self.is_loading
+            || self.is_processing
+            || self.load_rx.is_some()
+            || self.raw_peaks_rx.is_some()
+            || self.processed_peaks_rx.is_some()
+            || self.spectrogram_rx.is_some()
+            || self.spectrogram_detail_rx.is_some()
+            || self.envelope_rx.is_some()
+            || self.preview_rx.is_some()
+            || self.preview_changed_at.is_some()
+            || self.restart_changed_at.is_some()
+            || self.result_rx.is_some()
+            || self.stream_rx.is_some()
+            || self.export_rx.is_some()
+            || self.transport.state() == TransportState::Playing
+    }
+
+    pub fn update_processing(&mut self) {
+        self.restart_if_params_changed();
+        self.update_live_preview();
+        if let Some(result) = poll(&mut self.load_rx) {
+            self.is_loading = false;
+            match result {
+                Ok(Some((path, spec, samples))) => self.set_loaded(path, spec, samples),
+                Ok(None) => {}
+                Err(err) => {
+                    eprintln!("{}", err);
+                    self.status_message = err;
                 }
-                self.is_processing = false;
-                self.progress_rx = None;
-                self.result_rx = None;
             }
         }
-        // Handle stop signal
-        if let Some(rx) = self.stop_rx.take() { // Take and consume stop_rx
-            while let Ok(source) = rx.try_recv() {
-                match source {
-                    PlaybackSource::Raw => {
-                        self.raw_waveform.playing_stream = None;
-                        println!("Raw waveform playback finished and cleaned up");
-                    }
-                    PlaybackSource::Processed => {
-                        self.processed_waveform.playing_stream = None;
-                        println!("Processed waveform playback finished and cleaned up");
-                    }
+        if let Some((silence_segments, result_samples)) = poll(&mut self.result_rx) {
+            self.raw_waveform.silence_segments = silence_segments;
+            if let Some(samples) = result_samples {
+                self.timeline = TimelineMap::from_removed(
+                    &self.raw_waveform.silence_segments,
+                    self.raw_waveform.samples_raw.len(),
+                );
+                self.processed_waveform.samples_raw = Arc::new(SampleBuffer::Owned(samples));
+                self.processed_waveform.peaks = None;
+                self.processed_ready = true;
+                self.processed_view = WaveformView::default();
+                if self.transport.source() == Some(PlaybackSource::Processed) {
+                    self.transport.unload();
                 }
+                if self.selection.is_some_and(|(source, _, _)| source == PlaybackSource::Processed) {
+                    self.selection = None;
+                }
+                self.build_peaks_background(PlaybackSource::Processed);
+            }
+            self.is_processing = false;
+            self.processing_job = None;
+        }
+        if let Some(peaks) = poll(&mut self.raw_peaks_rx) {
+            let peaks = Arc::new(peaks);
+            // Until silence is removed, the processed waveform shares the original samples
+            if !self.processed_ready {
+                self.processed_waveform.peaks = Some(Arc::clone(&peaks));
             }
-            self.stop_rx = None;
+            self.raw_waveform.peaks = Some(peaks);
+        }
+        if let Some(peaks) = poll(&mut self.processed_peaks_rx) {
+            self.processed_waveform.peaks = Some(Arc::new(peaks));
+        }
+        if let Some(result) = poll(&mut self.stream_rx) {
+            self.status_message = result.unwrap_or_else(|err| err);
+            println!("{}", self.status_message);
+            self.is_processing = false;
+            self.processing_job = None;
         }
+        if let Some(result) = poll(&mut self.export_rx) {
+            self.status_message = result.unwrap_or_else(|err| err);
+            println!("{}", self.status_message);
+            self.is_processing = false;
+            self.processing_job = None;
+        }
+        if let Some(err) = self.transport.update() {
+            self.report_playback_error(Err(err));
+        }
+    }
+
+    /// Writes the processed audio, converted to `export_sample_rate` if one is set, in the background.
+    pub fn save_file(&mut self) {
+        if self.is_processing {
+            return;
+        }
+        let Some(spec) = self.spec else { return };
+        let Some(path) = FileDialog::new()
+            .add_filter("WAV", &["wav"])
+            .set_file_name("output.wav")
+            .save_file()
+        else {
+            return;
+        };
+        if self.overwrites_mapped_file(&path) {
+            self.status_message = format!("Cannot overwrite {:?} while it is open; choose another file", path);
+            return;
+        }
+        let (cancel, progress) = self.start_processing(ProcessingJob::Export);
+        let (export_tx, export_rx) = mpsc::channel();
+        self.export_rx = Some(export_rx);
+
+        let samples = Arc::clone(&self.processed_waveform.samples_raw);
+        let rate = self.export_sample_rate;
+        thread::spawn(move || {
+            let Some((spec, samples)) = for_export(spec, &samples, rate, &cancel, |f| progress.set(f)) else {
+                return;
+            };
+            let message = match write_wav(&path, spec, &samples) {
+                Ok(()) => Ok(format!("Saved to {:?}", path)),
+                Err(err) => Err(format!("Failed to save {:?}: {}", path, err)),
+            };
+            let _ = export_tx.send(message);
+        });
     }
 
-    pub fn save_file(&self) {
+    /// Exports the original/processed time mapping as CSV, e.g. for retiming subtitles.
+    pub fn export_timeline(&mut self) {
         if let Some(spec) = self.spec {
             if let Some(path) = FileDialog::new()
-                .add_filter("WAV", &["wav"])
-                .set_file_name("output.wav")
+                .add_filter("CSV", &["csv"])
+                .set_file_name("timeline.csv")
                 .save_file()
             {
-                if let Ok(mut writer) = WavWriter::create(&path, spec) {
-                    for &sample in self.processed_waveform.samples_raw.iter() {
-                        writer.write_sample(sample).unwrap();
+                match File::create(&path).and_then(|file| self.timeline.write_csv(BufWriter::new(file), spec)) {
+                    Ok(()) => {
+                        self.status_message = format!("Saved timeline to {:?}", path);
+                        println!("{}", self.status_message);
+                    }
+                    Err(err) => {
+                        self.status_message = format!("Failed to save timeline to {:?}: {}", path, err);
+                        eprintln!("{}", self.status_message);
                     }
-                    writer.finalize().unwrap();
-                    println!("Saved to {:?}", path);
                 }
             }
         }
     }
 
-    pub fn play_original(&mut self) {
-        if self.file_loaded && self.spec.is_some() {
-            if let Some(stream) = &self.processed_waveform.playing_stream {
-                stream.pause().expect("Failed to pause processed stream");
+    /// Loads an SRT or WebVTT file timed against the original audio and saves a copy
+    /// retimed to the processed audio.
+    pub fn retime_subtitles(&mut self) {
+        let Some(spec) = self.spec else { return };
+        let Some(input) = FileDialog::new().add_filter("Subtitles", &["srt", "vtt"]).pick_file() else {
+            return;
+        };
+        let format = input
+            .extension()
+            .and_then(|ext| SubtitleFormat::from_extension(&ext.to_string_lossy()))
+            .unwrap_or(SubtitleFormat::Srt);
+        let subtitles = match std::fs::read_to_string(&input)
+            .map_err(|err| err.to_string())
+            .and_then(|content| Subtitles::parse(&content, format))
+        {
+            Ok(subtitles) => subtitles,
+            Err(err) => {
+                self.status_message = format!("Failed to read subtitles {:?}: {}", input, err);
+                eprintln!("{}", self.status_message);
+                return;
+            }
+        };
+
+        let retimed = subtitles.retime(&self.timeline, spec);
+        let stem = input.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        if let Some(path) = FileDialog::new()
+            .add_filter("Subtitles", &[format.extension()])
+            .set_file_name(format!("{}_retimed.{}", stem, format.extension()))
+            .save_file()
+        {
+            match std::fs::write(&path, retimed.to_text()) {
+                Ok(()) => {
+                    self.status_message = format!(
+                        "Saved {} of {} subtitle cues to {:?}",
+                        retimed.cues.len(),
+                        subtitles.cues.len(),
+                        path
+                    );
+                    println!("{}", self.status_message);
+                }
+                Err(err) => {
+                    self.status_message = format!("Failed to save subtitles to {:?}: {}", path, err);
+                    eprintln!("{}", self.status_message);
+                }
             }
-            let samples = Arc::clone(&self.raw_waveform.samples_raw);
-            let spec = self.spec.unwrap();
-            let (stop_tx, stop_rx) = mpsc::channel();
-            self.stop_rx = Some(stop_rx);
-            println!("Playing original samples count: {}", samples.len());
-            play_samples(
-                &mut self.raw_waveform.playing_stream,
-                samples,
-                spec,
-                &self.raw_waveform.current_idx,
-                Some(stop_tx),
-                PlaybackSource::Raw,
-            );
-        }
-    }
-
-    pub fn play_processed(&mut self) {
-        if self.file_loaded && self.spec.is_some() && self.processed_ready {
-            if let Some(stream) = &self.raw_waveform.playing_stream {
-                stream.pause().expect("Failed to pause original stream");
+        }
+    }
+
+    /// Exports each non-silent region of the original audio as its own WAV file, in the background.
+    /// `{n}` in the name pattern is replaced by the segment number and `{start}` by its start time in ms.
+    pub fn export_split(&mut self) {
+        if self.is_processing {
+            return;
+        }
+        let Some(spec) = self.spec else { return };
+        let Some(dir) = FileDialog::new().pick_folder() else { return };
+        let samples = Arc::clone(&self.raw_waveform.samples_raw);
+        let channels = spec.channels as usize;
+        let min_samples = self.split_min_len * spec.sample_rate as usize / 1000 * channels;
+        // Path separators would let the pattern write outside the chosen folder
+        let pattern: String = self
+            .split_pattern
+            .chars()
+            .map(|c| if matches!(c, '/' | '\\' | ':') { '_' } else { c })
+            .collect();
+        let pattern = if pattern.contains("{n}") { pattern } else { format!("{}_{{n}}", pattern) };
+
+        let segments: Vec<(String, Range<usize>)> = non_silent_regions(&self.raw_waveform.silence_segments, samples.len())
+            .into_iter()
+            .filter(|&(s, e)| e - s >= min_samples)
+            .enumerate()
+            .map(|(i, (start, end))| {
+                let start_ms = (start / channels) as u64 * 1000 / spec.sample_rate as u64;
+                let name = pattern
+                    .replace("{n}", &format!("{:03}", i + 1))
+                    .replace("{start}", &start_ms.to_string());
+                (name, start..end)
+            })
+            .collect();
+        if let Some((name, _)) = segments.iter().find(|(name, _)| self.overwrites_mapped_file(&dir.join(format!("{}.wav", name)))) {
+            self.status_message = format!("Cannot overwrite {}.wav while it is open; choose another folder or name", name);
+            return;
+        }
+        let (cancel, progress) = self.start_processing(ProcessingJob::Export);
+        let (export_tx, export_rx) = mpsc::channel();
+        self.export_rx = Some(export_rx);
+
+        let rate = self.export_sample_rate;
+        thread::spawn(move || {
+            let total: usize = segments.iter().map(|(_, range)| range.len()).sum();
+            let mut done = 0;
+            let mut failed = Vec::new();
+            for (name, range) in &segments {
+                let on_progress = |f: f32| progress.set((done as f32 + f * range.len() as f32) / total.max(1) as f32);
+                let Some((spec, samples)) = for_export(spec, &samples[range.clone()], rate, &cancel, on_progress) else {
+                    return;
+                };
+                let path = dir.join(format!("{}.wav", name));
+                if let Err(err) = write_wav(&path, spec, &samples) {
+                    eprintln!("Failed to write {:?}: {}", path, err);
+                    failed.push(format!("{}.wav: {}", name, err));
+                }
+                done += range.len();
             }
-            let samples = Arc::clone(&self.processed_waveform.samples_raw);
-            let spec = self.spec.unwrap();
-            let (stop_tx, stop_rx) = mpsc::channel();
-            self.stop_rx = Some(stop_rx);
-            println!("Playing processed samples count: {}", samples.len());
-            play_samples(
-                &mut self.processed_waveform.playing_stream,
-                samples,
-                spec,
-                &self.processed_waveform.current_idx,
-                Some(stop_tx),
-                PlaybackSource::Processed,
-            );
+            let message = if failed.is_empty() {
+                Ok(format!("Exported {} segments to {:?}", segments.len(), dir))
+            } else {
+                Err(format!("Failed to export {} of {} segments: {}", failed.len(), segments.len(), failed.join("; ")))
+            };
+            let _ = export_tx.send(message);
+        });
+    }
+
+    /// The waveform, buffer and sample range `source` plays, if it is available.
+    fn source_range(&self, source: TransportSource) -> Option<(PlaybackSource, Arc<SampleBuffer>, Range<usize>)> {
+        if !self.file_loaded {
+            return None;
         }
+        let waveform = match source {
+            TransportSource::Original => PlaybackSource::Raw,
+            TransportSource::Processed if self.processed_ready => PlaybackSource::Processed,
+            TransportSource::Selection => self.selection?.0,
+            TransportSource::Processed => return None,
+        };
+        let samples = match waveform {
+            PlaybackSource::Raw => Arc::clone(&self.raw_waveform.samples_raw),
+            PlaybackSource::Processed => Arc::clone(&self.processed_waveform.samples_raw),
+        };
+        let range = match (source, self.selection) {
+            (TransportSource::Selection, Some((_, start, end))) => start..end,
+            _ => 0..samples.len(),
+        };
+        Some((waveform, samples, range))
     }
 
-    pub fn pause_original(&mut self) {
-        if let Some(stream) = &self.raw_waveform.playing_stream {
-            stream.pause().expect("Failed to pause original stream");
+    /// Plays the current source, resuming if paused. A stopped transport starts from the
+    /// position cued by clicking on the waveform, or from the start.
+    pub fn play(&mut self) {
+        match self.transport.state() {
+            TransportState::Playing => {}
+            TransportState::Paused => {
+                let result = self.transport.play();
+                self.report_playback_error(result);
+            }
+            TransportState::Stopped => {
+                let (Some((waveform, samples, range)), Some(spec)) = (self.source_range(self.transport_source), self.spec) else {
+                    return;
+                };
+                let cued = self.transport.source() == Some(waveform) && self.transport.range() == range;
+                let position = if cued { self.transport.position() } else { range.start };
+                println!("Playing {:?} samples {:?}", self.transport_source, range);
+                let result = self
+                    .transport
+                    .load(waveform, samples, range, position, spec, self.output_device.as_deref())
+                    .and_then(|_| self.transport.play());
+                self.report_playback_error(result);
+            }
         }
     }
 
-    pub fn pause_processed(&mut self) {
-        if let Some(stream) = &self.processed_waveform.playing_stream {
-            stream.pause().expect("Failed to pause processed stream");
+    /// Stops whatever is playing and plays `source` instead.
+    pub fn play_source(&mut self, source: TransportSource) {
+        if self.transport_source != source {
+            self.transport.stop();
+            self.transport_source = source;
         }
+        self.play();
     }
 
-    pub fn resume_original(&mut self) {
-        if let Some(stream) = &self.processed_waveform.playing_stream {
-            stream.pause().expect("Failed to pause original stream");
+    /// Changes the source. While playing or paused, the new source continues from the same
+    /// sample if it has one, and from its start otherwise, without reopening the device.
+    pub fn switch_source(&mut self, source: TransportSource) {
+        self.transport_source = source;
+        if self.transport.state() == TransportState::Stopped {
+            return;
         }
-        if let Some(stream) = &self.raw_waveform.playing_stream {
-            stream.play().expect("Failed to resume original stream");
+        let (Some((waveform, samples, range)), Some(spec)) = (self.source_range(source), self.spec) else {
+            self.transport.stop();
+            return;
+        };
+        // Keep the moment of the recording that is playing, or start over if it is not in the new range
+        let position = match self.transport.source() {
+            Some(from) => self.timeline.map_position(from, waveform, self.transport.position()),
+            None => range.start,
+        };
+        let position = if range.contains(&position) { position } else { range.start };
+        let result = self.transport.load(waveform, samples, range, position, spec, self.output_device.as_deref());
+        self.report_playback_error(result);
+    }
+
+    /// Flips between the original and processed audio at the same moment of the recording,
+    /// mapping the position through the removed segments. A position inside removed silence
+    /// lands where the processed audio resumes after the cut.
+    pub fn toggle_ab(&mut self) {
+        if !self.processed_ready {
+            return;
         }
+        let (from, source) = match self.transport.source() {
+            Some(from @ PlaybackSource::Raw) => (from, TransportSource::Processed),
+            Some(from @ PlaybackSource::Processed) => (from, TransportSource::Original),
+            None => {
+                self.transport_source = match self.transport_source {
+                    TransportSource::Processed => TransportSource::Original,
+                    _ => TransportSource::Processed,
+                };
+                return;
+            }
+        };
+        let (Some((waveform, samples, range)), Some(spec)) = (self.source_range(source), self.spec) else {
+            return;
+        };
+        self.transport_source = source;
+        let position = self.timeline.map_position(from, waveform, self.transport.position());
+        // Loading keeps the play state, so the switch is heard from the next buffer on
+        let result = self.transport.load(waveform, samples, range, position, spec, self.output_device.as_deref());
+        self.report_playback_error(result);
+    }
+
+    pub fn pause(&mut self) {
+        self.transport.pause();
     }
 
-    pub fn resume_processed(&mut self) {
-        if let Some(stream) = &self.raw_waveform.playing_stream {
-            stream.pause().expect("Failed to pause original stream");
+    pub fn stop(&mut self) {
+        self.transport.stop();
+    }
+
+    /// Refreshes the list of output devices shown in the device picker.
+    pub fn refresh_output_devices(&mut self) {
+        let devices = output_device_names();
+        if self.output_device.as_ref().is_some_and(|name| !devices.contains(name)) {
+            self.output_device = None;
         }
-        if let Some(stream) = &self.processed_waveform.playing_stream {
-            stream.play().expect("Failed to resume processed stream");
+        self.output_devices = Some(devices);
+    }
+
+    /// Switches playback to another output device. Whatever is loaded moves to the new device
+    /// right away, keeping its position and play state.
+    pub fn set_output_device(&mut self, device: Option<String>) {
+        self.output_device = device;
+        let (Some(waveform), Some(spec)) = (self.transport.source(), self.spec) else {
+            return;
+        };
+        let samples = match waveform {
+            PlaybackSource::Raw => Arc::clone(&self.raw_waveform.samples_raw),
+            PlaybackSource::Processed => Arc::clone(&self.processed_waveform.samples_raw),
+        };
+        let position = self.transport.position();
+        let range = self.transport.range();
+        let result = self.transport.load(waveform, samples, range, position, spec, self.output_device.as_deref());
+        self.report_playback_error(result);
+    }
+
+    fn report_playback_error(&mut self, result: Result<(), String>) {
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            self.status_message = err;
         }
     }
 
-    pub fn stop_original(&mut self) {
-        self.raw_waveform.playing_stream = None;
-        *self.raw_waveform.current_idx.lock().unwrap() = 0;
+    pub fn view_mut(&mut self, source: PlaybackSource) -> &mut WaveformView {
+        match source {
+            PlaybackSource::Raw => &mut self.raw_view,
+            PlaybackSource::Processed => &mut self.processed_view,
+        }
     }
 
-    pub fn stop_processed(&mut self) {
-        self.processed_waveform.playing_stream = None;
-        *self.processed_waveform.current_idx.lock().unwrap() = 0;
+    /// With `link_views` on, makes the view that was not last moved show the same audio as
+    /// `view_leader` at the same time scale, centered on the matching position.
+    pub fn sync_linked_views(&mut self, width: f32) {
+        if !self.link_views || !self.processed_ready {
+            return;
+        }
+        let channels = self.spec.map_or(1, |spec| spec.channels) as f64;
+        let raw_total = self.raw_waveform.samples_raw.len() as f64;
+        let processed_total = self.processed_waveform.samples_raw.len() as f64;
+        let (leader, follower, leader_total, follower_total) = match self.view_leader {
+            PlaybackSource::Raw => (self.raw_view, &mut self.processed_view, raw_total, processed_total),
+            PlaybackSource::Processed => (self.processed_view, &mut self.raw_view, processed_total, raw_total),
+        };
+        if leader_total == 0.0 || follower_total == 0.0 {
+            return;
+        }
+
+        let samples_per_pixel = leader.samples_per_pixel(leader_total, width);
+        let center = leader.sample_at(width / 2.0, leader_total, width) as usize;
+        let mapped = match self.view_leader {
+            PlaybackSource::Raw => self.timeline.original_to_processed(center),
+            PlaybackSource::Processed => self.timeline.processed_to_original(center),
+        };
+        let max_zoom = WaveformView::max_zoom(follower_total, channels, width);
+        follower.zoom = (follower_total / width as f64 / samples_per_pixel).clamp(1.0, max_zoom);
+        follower.center_on(mapped as f64, follower_total, width);
     }
 
+    /// Moves playback to `sample_idx` of a waveform. Clicking on the waveform that isn't loaded
+    /// switches the transport to it, playing on if it was playing.
     pub fn jump_to_position(&mut self, sample_idx: usize, is_original: bool) {
-        let waveform = if is_original { &mut self.raw_waveform } else { &mut self.processed_waveform };
-        *waveform.current_idx.lock().unwrap() = sample_idx.min(waveform.samples_raw.len());
+        let waveform = if is_original { PlaybackSource::Raw } else { PlaybackSource::Processed };
+        if self.transport.source() == Some(waveform) && self.transport.range().contains(&sample_idx) {
+            self.transport.seek(sample_idx);
+            return;
+        }
+        let source = if is_original { TransportSource::Original } else { TransportSource::Processed };
+        let (Some((waveform, samples, range)), Some(spec)) = (self.source_range(source), self.spec) else {
+            return;
+        };
+        self.transport_source = source;
+        let result = self.transport.load(waveform, samples, range, sample_idx, spec, self.output_device.as_deref());
+        self.report_playback_error(result);
     }
 }
\ No newline at end of file
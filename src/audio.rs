@@ -1,9 +1,17 @@
-use cpal::traits::{HostTrait, DeviceTrait, StreamTrait};
+use cpal::traits::{HostTrait, DeviceTrait};
 use hound::WavSpec;
-use std::sync::{Arc, Mutex, mpsc::Sender};
+use crate::job::CancelToken;
+use crate::peaks::PeakPyramid;
+use crate::wav::SampleBuffer;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// Number of frames each detection thread scans between progress reports and cancellation checks.
+const DETECT_BLOCK_FRAMES: usize = 1 << 16;
 
 // Add enum type to represent playback source
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PlaybackSource {
     Raw,
     Processed,
@@ -11,85 +19,333 @@ pub enum PlaybackSource {
 
 #[derive(Clone)]
 pub struct WaveformData {
-    pub samples_raw: Arc<Vec<i16>>,
-    pub current_idx: Arc<Mutex<usize>>,
-    pub playing_stream: Option<Arc<cpal::Stream>>,
+    pub samples_raw: Arc<SampleBuffer>,
     pub silence_segments: Vec<(usize, usize)>,
+    pub peaks: Option<Arc<PeakPyramid>>,
+}
+
+impl Default for WaveformData {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl WaveformData {
     pub fn new() -> Self {
         Self {
-            samples_raw: Arc::new(Vec::new()),
-            current_idx: Arc::new(Mutex::new(0)),
-            playing_stream: None,
+            samples_raw: Arc::new(SampleBuffer::Owned(Vec::new())),
             silence_segments: Vec::new(),
+            peaks: None,
         }
     }
 
     pub fn from_samples(samples_raw: Vec<i16>) -> Self {
+        Self::from_shared(Arc::new(SampleBuffer::Owned(samples_raw)))
+    }
+
+    /// Creates a waveform that shares its sample buffer with another one.
+    pub fn from_shared(samples_raw: Arc<SampleBuffer>) -> Self {
         Self {
-            samples_raw: Arc::new(samples_raw),
-            current_idx: Arc::new(Mutex::new(0)),
-            playing_stream: None,
+            samples_raw,
             silence_segments: Vec::new(),
+            peaks: None,
+        }
+    }
+}
+
+/// Frame-by-frame silence detector shared by the in-memory and streaming processing paths.
+/// Segments are reported as interleaved sample indices `(start, end)`.
+#[derive(Clone)]
+pub struct SilenceDetector {
+    threshold: f32,
+    min_frames: usize,
+    silence_count: usize,
+    silence_start: usize,
+}
+
+impl SilenceDetector {
+    pub fn new(threshold: f32, min_silence_len: usize, spec: WavSpec) -> Self {
+        Self {
+            threshold,
+            min_frames: min_silence_len * spec.sample_rate as usize / 1000,
+            silence_count: 0,
+            silence_start: 0,
         }
     }
+
+    /// Average absolute amplitude of one interleaved frame, in the range 0.0..=1.0.
+    pub fn frame_amplitude(frame: &[i16], channels: usize) -> f32 {
+        frame.iter().map(|&s| (s as f32).abs() / i16::MAX as f32).sum::<f32>() / channels as f32
+    }
+
+    /// Feeds the frame starting at sample index `idx`.
+    /// Returns the silence segment that this frame ends, if it was long enough.
+    pub fn push(&mut self, idx: usize, amplitude: f32) -> Option<(usize, usize)> {
+        if amplitude < self.threshold {
+            if self.silence_count == 0 {
+                self.silence_start = idx;
+            }
+            self.silence_count += 1;
+            None
+        } else if self.silence_count > 0 {
+            let segment = (self.silence_count >= self.min_frames).then_some((self.silence_start, idx));
+            self.silence_count = 0;
+            segment
+        } else {
+            None
+        }
+    }
+
+    /// Returns the trailing silence segment, if the audio ends in one that is long enough.
+    pub fn finish(&mut self, total_samples: usize) -> Option<(usize, usize)> {
+        let segment = (self.silence_count > 0 && self.silence_count >= self.min_frames)
+            .then_some((self.silence_start, total_samples));
+        self.silence_count = 0;
+        segment
+    }
+
+    /// Number of consecutive silent frames seen so far.
+    pub fn silence_frames(&self) -> usize {
+        self.silence_count
+    }
+
+    pub fn min_frames(&self) -> usize {
+        self.min_frames
+    }
+}
+
+/// Silence found in one chunk of a parallel scan. Runs touching the chunk edges are reported
+/// separately, since whether they are long enough depends on the neighbouring chunks.
+struct ChunkScan {
+    /// Silent frames at the start of the chunk; equal to the chunk length if it is all silent.
+    leading_frames: usize,
+    all_silent: bool,
+    segments: Vec<(usize, usize)>,
+    /// Start sample and length in frames of a silent run still open at the end of the chunk.
+    trailing: Option<(usize, usize)>,
+}
+
+fn scan_chunk(
+    samples: &[i16],
+    offset: usize,
+    channels: usize,
+    mut detector: SilenceDetector,
+    cancel: &CancelToken,
+    on_block: &(dyn Fn(usize) + Sync),
+) -> Option<ChunkScan> {
+    let mut leading_frames = None;
+    let mut segments = Vec::new();
+
+    for (block_idx, block) in samples.chunks(DETECT_BLOCK_FRAMES * channels).enumerate() {
+        if cancel.is_cancelled() {
+            return None;
+        }
+        let block_offset = block_idx * DETECT_BLOCK_FRAMES;
+        for (frame_idx, frame) in block.chunks(channels).enumerate() {
+            let amplitude = SilenceDetector::frame_amplitude(frame, channels);
+            if leading_frames.is_none() {
+                if amplitude >= detector.threshold {
+                    leading_frames = Some(block_offset + frame_idx);
+                }
+                continue;
+            }
+            let i = offset + (block_offset + frame_idx) * channels;
+            if let Some(segment) = detector.push(i, amplitude) {
+                segments.push(segment);
+            }
+        }
+        on_block(block.len().div_ceil(channels));
+    }
+
+    let total_frames = samples.len().div_ceil(channels);
+    Some(ChunkScan {
+        leading_frames: leading_frames.unwrap_or(total_frames),
+        all_silent: leading_frames.is_none(),
+        segments,
+        trailing: (detector.silence_count > 0).then_some((detector.silence_start, detector.silence_count)),
+    })
 }
 
-pub fn play_samples(
-    stream: &mut Option<Arc<cpal::Stream>>,
-    samples: Arc<Vec<i16>>,
+/// Detects silence using all available cores. The samples are split into chunks at frame
+/// boundaries and scanned in parallel, then runs crossing chunk boundaries are stitched
+/// together, so the result is identical to a single `SilenceDetector` pass.
+/// Returns `None` if the token was cancelled.
+pub fn detect_silence_parallel<F: Fn(f32) + Sync>(
+    samples: &[i16],
     spec: WavSpec,
-    current_idx: &Arc<Mutex<usize>>,
-    stop_tx: Option<Sender<PlaybackSource>>,
-    source: PlaybackSource,
-) {
-    let sample_len = samples.len();
-    let host = cpal::default_host();
-    let device = host.default_output_device().expect("No output device available");
-    let config = cpal::StreamConfig {
-        channels: spec.channels,
-        sample_rate: cpal::SampleRate(spec.sample_rate),
-        buffer_size: cpal::BufferSize::Default,
+    threshold: f32,
+    min_silence_len: usize,
+    cancel: &CancelToken,
+    on_progress: F,
+) -> Option<Vec<(usize, usize)>> {
+    let total_frames = samples.len().div_ceil(spec.channels as usize);
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_frames = total_frames.div_ceil(threads).max(DETECT_BLOCK_FRAMES);
+    detect_silence_chunked(samples, spec, threshold, min_silence_len, chunk_frames, cancel, on_progress)
+}
+
+/// `detect_silence_parallel` with one thread per `chunk_frames` frames. Public so the stitching
+/// can be tested at chunk sizes small enough to put every boundary inside a run.
+pub fn detect_silence_chunked<F: Fn(f32) + Sync>(
+    samples: &[i16],
+    spec: WavSpec,
+    threshold: f32,
+    min_silence_len: usize,
+    chunk_frames: usize,
+    cancel: &CancelToken,
+    on_progress: F,
+) -> Option<Vec<(usize, usize)>> {
+    let channels = spec.channels as usize;
+    let total_frames = samples.len().div_ceil(channels);
+    let detector = SilenceDetector::new(threshold, min_silence_len, spec);
+    let min_frames = detector.min_frames;
+
+    let frames_done = AtomicUsize::new(0);
+    let on_block = |frames: usize| {
+        let done = frames_done.fetch_add(frames, Ordering::Relaxed) + frames;
+        on_progress(done as f32 / total_frames as f32);
     };
 
-    let samples = Arc::clone(&samples);
-    let current_idx = Arc::clone(current_idx);
-    let stop_tx_clone = stop_tx.clone();
-    *current_idx.lock().unwrap() = 0;
+    let scans = thread::scope(|scope| {
+        let handles: Vec<_> = samples
+            .chunks(chunk_frames * channels)
+            .enumerate()
+            .map(|(chunk_idx, chunk)| {
+                let detector = detector.clone();
+                let on_block = &on_block;
+                scope.spawn(move || scan_chunk(chunk, chunk_idx * chunk_frames * channels, channels, detector, cancel, on_block))
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect::<Option<Vec<_>>>()
+    })?;
+
+    // Stitch the chunks together, carrying a silent run across boundaries
+    let mut silence_segments = Vec::new();
+    let mut carry: Option<(usize, usize)> = None;
+    for (chunk_idx, scan) in scans.into_iter().enumerate() {
+        let chunk_start = chunk_idx * chunk_frames * channels;
+        if scan.all_silent {
+            let (start, frames) = carry.unwrap_or((chunk_start, 0));
+            carry = Some((start, frames + scan.leading_frames));
+            continue;
+        }
+        if carry.is_some() || scan.leading_frames > 0 {
+            let (start, frames) = carry.unwrap_or((chunk_start, 0));
+            if frames + scan.leading_frames >= min_frames {
+                silence_segments.push((start, chunk_start + scan.leading_frames * channels));
+            }
+        }
+        silence_segments.extend(scan.segments);
+        carry = scan.trailing;
+    }
+    if let Some((start, frames)) = carry {
+        if frames >= min_frames {
+            silence_segments.push((start, samples.len()));
+        }
+    }
+    Some(silence_segments)
+}
+
+/// Returns the regions between silence segments, i.e. the parts of the audio that are kept.
+pub fn non_silent_regions(silence_segments: &[(usize, usize)], total_samples: usize) -> Vec<(usize, usize)> {
+    let mut regions = Vec::new();
+    let mut last_end = 0;
+    for &(start, end) in silence_segments {
+        if start > last_end {
+            regions.push((last_end, start));
+        }
+        last_end = last_end.max(end);
+    }
+    if last_end < total_samples {
+        regions.push((last_end, total_samples));
+    }
+    regions
+}
 
-    let audio_stream = device
+/// Names of the output devices of the default host.
+pub fn output_device_names() -> Vec<String> {
+    let host = cpal::default_host();
+    match host.output_devices() {
+        Ok(devices) => devices.filter_map(|device| device.name().ok()).collect(),
+        Err(err) => {
+            eprintln!("Failed to list output devices: {}", err);
+            Vec::new()
+        }
+    }
+}
+
+/// Finds the output device called `name`, or the default device if `name` is `None`.
+pub(crate) fn output_device(name: Option<&str>) -> Result<cpal::Device, String> {
+    let host = cpal::default_host();
+    match name {
+        None => host.default_output_device().ok_or_else(|| String::from("No output device available")),
+        Some(name) => host
+            .output_devices()
+            .map_err(|err| format!("Failed to list output devices: {}", err))?
+            .find(|device| device.name().is_ok_and(|n| n == name))
+            .ok_or_else(|| format!("Output device {:?} is no longer available", name)),
+    }
+}
+
+/// Picks the device configuration that best fits the file: the file's sample rate if the
+/// device supports it, else the closest supported rate (playback is then resampled), then the
+/// file's channel count, then `f32` samples, then the other supported formats.
+pub(crate) fn negotiate_config(device: &cpal::Device, spec: WavSpec) -> Result<cpal::SupportedStreamConfig, String> {
+    let format_rank = |format: cpal::SampleFormat| match format {
+        cpal::SampleFormat::F32 => 0,
+        cpal::SampleFormat::I16 => 1,
+        cpal::SampleFormat::I32 => 2,
+        cpal::SampleFormat::U16 => 3,
+        _ => 4,
+    };
+    let rate = spec.sample_rate;
+    device
+        .supported_output_configs()
+        .map_err(|err| format!("Failed to query output device: {}", err))?
+        .filter(|range| format_rank(range.sample_format()) < 4)
+        .map(|range| {
+            let closest = rate.clamp(range.min_sample_rate().0, range.max_sample_rate().0);
+            (range, closest)
+        })
+        .min_by_key(|(range, closest)| {
+            (closest.abs_diff(rate), range.channels() != spec.channels, format_rank(range.sample_format()))
+        })
+        .map(|(range, closest)| range.with_sample_rate(cpal::SampleRate(closest)))
+        .ok_or_else(|| String::from("The output device has no supported output format"))
+}
+
+/// Builds an output stream in the device's sample format; `render` always fills `f32` samples.
+/// Errors reported by the running stream are sent to `errors`.
+pub(crate) fn build_stream<T, R>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut render: R,
+    errors: mpsc::Sender<String>,
+) -> Result<cpal::Stream, String>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+    R: FnMut(&mut [f32]) + Send + 'static,
+{
+    let mut scratch = [0.0f32; 1024];
+    let channels = config.channels as usize;
+    device
         .build_output_stream(
-            &config,
-            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                let mut idx = current_idx.lock().unwrap();
-                let mut all_played = false;
-                for frame in data.chunks_mut(spec.channels as usize) {
-                    for sample in frame {
-                        if *idx < sample_len {
-                            *sample = samples[*idx] as f32 / i16::MAX as f32;
-                            *idx += 1;
-                        } else {
-                            *sample = 0.0;
-                            all_played = true;
-                        }
-                    }
-                }
-                // Check if all samples have been played
-                if all_played && *idx >= sample_len {
-                    if let Some(ref tx) = stop_tx_clone {
-                        let _ = tx.send(source.clone()); // Notify the main thread to stop
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                // Render in whole frames through a fixed buffer, so the callback never allocates
+                let chunk_len = scratch.len() / channels * channels;
+                for chunk in data.chunks_mut(chunk_len) {
+                    let scratch = &mut scratch[..chunk.len()];
+                    render(scratch);
+                    for (out, &sample) in chunk.iter_mut().zip(scratch.iter()) {
+                        *out = T::from_sample(sample);
                     }
                 }
             },
-            |err| eprintln!("Audio error: {}", err),
+            move |err| {
+                let _ = errors.send(format!("Audio error: {}", err));
+            },
             None,
         )
-        .expect("Failed to build output stream");
-
-    audio_stream.play().expect("Failed to play stream");
-
-    let audio_stream = Arc::new(audio_stream);
-    *stream = Some(Arc::clone(&audio_stream));
-}
\ No newline at end of file
+        .map_err(|err| format!("Failed to open the output device: {}", err))
+}
@@ -0,0 +1,76 @@
+use crate::audio::SilenceDetector;
+use crate::job::CancelToken;
+use crate::wav::SampleBuffer;
+use hound::WavSpec;
+use std::sync::Arc;
+
+/// Number of frames processed between cancellation checks.
+const CANCEL_CHECK_FRAMES: usize = 1 << 16;
+
+/// Per-frame amplitude of a waveform, precomputed so silence can be re-detected with new
+/// parameters without rescanning the raw samples. Amplitudes are quantized to 16 bits, which
+/// keeps the envelope at most the size of a mono file.
+pub struct Envelope {
+    frames: Vec<u16>,
+    samples: Arc<SampleBuffer>,
+    channels: usize,
+}
+
+impl Envelope {
+    /// Returns `None` if the token is cancelled first.
+    pub fn compute(samples: Arc<SampleBuffer>, channels: usize, cancel: &CancelToken) -> Option<Self> {
+        let mut frames = Vec::with_capacity(samples.len().div_ceil(channels));
+        for block in samples.chunks(CANCEL_CHECK_FRAMES * channels) {
+            if cancel.is_cancelled() {
+                return None;
+            }
+            frames.extend(block.chunks(channels).map(|frame| {
+                let amplitude = SilenceDetector::frame_amplitude(frame, channels);
+                (amplitude * u16::MAX as f32).round() as u16
+            }));
+        }
+        Some(Self {
+            frames,
+            samples,
+            channels,
+        })
+    }
+
+    /// Quantized amplitude of a frame, within half a step of 1 / 65535 of the exact value.
+    pub fn amplitude(&self, frame_idx: usize) -> f32 {
+        self.frames[frame_idx] as f32 / u16::MAX as f32
+    }
+
+    /// Detects silence from the envelope, with the same result as scanning the samples.
+    /// Returns `None` if the token is cancelled first.
+    pub fn detect(
+        &self,
+        spec: WavSpec,
+        threshold: f32,
+        min_silence_len: usize,
+        cancel: &CancelToken,
+    ) -> Option<Vec<(usize, usize)>> {
+        let mut detector = SilenceDetector::new(threshold, min_silence_len, spec);
+        let mut silence_segments = Vec::new();
+        let quantized_threshold = threshold * u16::MAX as f32;
+        for frame_idx in 0..self.frames.len() {
+            if frame_idx % CANCEL_CHECK_FRAMES == 0 && cancel.is_cancelled() {
+                return None;
+            }
+            let start = frame_idx * self.channels;
+            // Rounding can move a frame this close to the threshold to its other side, so
+            // those few frames are measured again from the samples
+            let amplitude = if (self.frames[frame_idx] as f32 - quantized_threshold).abs() <= 1.0 {
+                let end = (start + self.channels).min(self.samples.len());
+                SilenceDetector::frame_amplitude(&self.samples[start..end], self.channels)
+            } else {
+                self.amplitude(frame_idx)
+            };
+            if let Some(segment) = detector.push(start, amplitude) {
+                silence_segments.push(segment);
+            }
+        }
+        silence_segments.extend(detector.finish(self.samples.len()));
+        Some(silence_segments)
+    }
+}
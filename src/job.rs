@@ -0,0 +1,63 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Cancellation flag shared between the UI and a background worker.
+/// Workers check it periodically and stop early once it is set.
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Progress of a background job, shared between the worker and the UI.
+/// Workers may update it as often as they like; the UI reads the latest value once per frame.
+#[derive(Clone)]
+pub struct Progress {
+    fraction: Arc<AtomicU32>,
+    started: Instant,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Self {
+            fraction: Arc::new(AtomicU32::new(0.0f32.to_bits())),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn set(&self, fraction: f32) {
+        self.fraction.store(fraction.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn fraction(&self) -> f32 {
+        f32::from_bits(self.fraction.load(Ordering::Relaxed))
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Estimated time remaining, extrapolated from the rate so far.
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction();
+        (fraction > 0.01).then(|| self.elapsed().mul_f32((1.0 - fraction) / fraction))
+    }
+}
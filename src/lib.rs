@@ -0,0 +1,13 @@
+pub mod app;
+pub mod audio;
+pub mod envelope;
+pub mod job;
+pub mod peaks;
+pub mod playback;
+pub mod resample;
+pub mod spectrogram;
+pub mod stream;
+pub mod subtitle;
+pub mod timeline;
+pub mod ui;
+pub mod wav;
@@ -1,12 +1,18 @@
 use eframe::NativeOptions;
 use eframe::egui::Vec2;
-use app::SoundApp;
+use solid_meme::app::SoundApp;
+use solid_meme::job::CancelToken;
+use solid_meme::stream;
+use std::path::Path;
 
-mod app;
-mod audio;
-mod ui;
+const USAGE: &str = "Usage: solid-meme [<input.wav> <output.wav> [--threshold <amplitude>] [--min-silence <ms>]]";
 
 fn main() -> Result<(), eframe::Error> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(run_headless(&args));
+    }
+
     let options = NativeOptions {
         initial_window_size: Some(Vec2::new(800.0, 600.0)),
         ..Default::default()
@@ -16,4 +22,68 @@ fn main() -> Result<(), eframe::Error> {
         options,
         Box::new(|_cc| Box::new(SoundApp::new())),
     )
-}
\ No newline at end of file
+}
+
+/// Removes silence from a file without opening the window, streaming it from disk.
+fn run_headless(args: &[String]) -> i32 {
+    let defaults = SoundApp::new();
+    let mut threshold = defaults.silence_threshold;
+    let mut min_silence_len = defaults.min_silence_len;
+    let mut paths = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--threshold" => match iter.next().and_then(|v| v.parse().ok()) {
+                Some(v) => threshold = v,
+                None => return invalid_value(arg),
+            },
+            "--min-silence" => match iter.next().and_then(|v| v.parse().ok()) {
+                Some(v) => min_silence_len = v,
+                None => return invalid_value(arg),
+            },
+            _ => paths.push(arg.as_str()),
+        }
+    }
+    let [input, output] = paths[..] else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+
+    let mut last_percent = 0;
+    let result = stream::remove_silence_streaming(
+        Path::new(input),
+        Path::new(output),
+        threshold,
+        min_silence_len,
+        &CancelToken::new(),
+        |progress| {
+            let percent = (progress * 100.0) as usize;
+            if percent >= last_percent + 10 {
+                last_percent = percent;
+                eprintln!("{}%", percent);
+            }
+        },
+    );
+    match result {
+        Ok(summary) => {
+            println!(
+                "Removed {} silence segments, saved {} of {} samples to {}",
+                summary.silence_segments.len(),
+                summary.samples_written,
+                summary.samples_read,
+                output
+            );
+            0
+        }
+        Err(err) => {
+            eprintln!("Failed to process {}: {}", input, err);
+            1
+        }
+    }
+}
+
+fn invalid_value(arg: &str) -> i32 {
+    eprintln!("Invalid value for {}\n{}", arg, USAGE);
+    2
+}
@@ -0,0 +1,270 @@
+use crate::job::CancelToken;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Number of samples summarized by one peak at the finest level.
+pub const BASE_BLOCK: usize = 256;
+
+const SIDECAR_MAGIC: &[u8; 4] = b"SMPK";
+const SIDECAR_VERSION: u32 = 2;
+
+/// Minimum, maximum and mean square of a block of samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Peak {
+    pub min: i16,
+    pub max: i16,
+    pub mean_square: f32,
+}
+
+impl Peak {
+    fn from_samples(samples: &[i16]) -> Self {
+        let mut peak = Peak { min: i16::MAX, max: i16::MIN, mean_square: 0.0 };
+        let mut sum_squares = 0.0f64;
+        for &s in samples {
+            peak.min = peak.min.min(s);
+            peak.max = peak.max.max(s);
+            sum_squares += (s as f64) * (s as f64);
+        }
+        peak.mean_square = (sum_squares / samples.len() as f64) as f32;
+        peak
+    }
+
+    fn merge(peaks: &[Peak]) -> Self {
+        let mut merged = Peak { min: i16::MAX, max: i16::MIN, mean_square: 0.0 };
+        for peak in peaks {
+            merged.min = merged.min.min(peak.min);
+            merged.max = merged.max.max(peak.max);
+            merged.mean_square += peak.mean_square / peaks.len() as f32;
+        }
+        merged
+    }
+
+    pub fn rms(&self) -> f32 {
+        self.mean_square.sqrt()
+    }
+}
+
+/// Mipmap-style summary of a waveform: level `n` holds one `Peak` per `BASE_BLOCK << n` frames,
+/// so drawing touches a bounded number of peaks per pixel at any zoom level. There is one
+/// pyramid per channel, plus one for the mono mixdown of multi-channel files.
+pub struct PeakPyramid {
+    lanes: Vec<Vec<Vec<Peak>>>,
+    channels: usize,
+    len: usize,
+}
+
+impl PeakPyramid {
+    /// Returns `None` if the token is cancelled first.
+    pub fn build(samples: &[i16], channels: usize, cancel: &CancelToken) -> Option<Self> {
+        let frames = samples.len() / channels;
+        let lane_count = if channels > 1 { channels + 1 } else { 1 };
+        let mut bases = vec![Vec::with_capacity(frames.div_ceil(BASE_BLOCK)); lane_count];
+        let mut block = Vec::with_capacity(BASE_BLOCK);
+        for (i, chunk) in samples.chunks(BASE_BLOCK * channels).enumerate() {
+            if i % 4096 == 0 && cancel.is_cancelled() {
+                return None;
+            }
+            let chunk = &chunk[..chunk.len() - chunk.len() % channels];
+            if chunk.is_empty() {
+                break;
+            }
+            for (channel, base) in bases.iter_mut().enumerate().take(channels) {
+                block.clear();
+                block.extend(chunk.iter().skip(channel).step_by(channels));
+                base.push(Peak::from_samples(&block));
+            }
+            if channels > 1 {
+                block.clear();
+                block.extend(chunk.chunks(channels).map(|frame| {
+                    (frame.iter().map(|&s| s as i32).sum::<i32>() / channels as i32) as i16
+                }));
+                bases[channels].push(Peak::from_samples(&block));
+            }
+        }
+
+        let lanes = bases
+            .into_iter()
+            .map(|base| {
+                let mut levels = vec![base];
+                while levels.last().is_some_and(|level| level.len() > 1) {
+                    let next = levels.last().unwrap().chunks(2).map(Peak::merge).collect();
+                    levels.push(next);
+                }
+                levels
+            })
+            .collect();
+        Some(Self { lanes, channels, len: frames })
+    }
+
+    /// Lane holding the mono mixdown; the only lane of a mono file.
+    pub fn mixdown_lane(&self) -> usize {
+        self.lanes.len() - 1
+    }
+
+    /// Summarizes exactly frames `from..to` of a lane. Whole blocks inside the range come from
+    /// the pyramid, a few per level, and the partial blocks at either edge are read with
+    /// `frame_value`, so audio just outside the range never shows up in it. Returns `None` when
+    /// the range is shorter than a block, where the raw samples are cheap enough to scan directly.
+    pub fn range<F: Fn(usize) -> i16>(&self, lane: usize, from: usize, to: usize, frame_value: F) -> Option<Peak> {
+        let to = to.min(self.len);
+        let levels = self.lanes.get(lane)?;
+        if to <= from || to - from < BASE_BLOCK || levels[0].is_empty() {
+            return None;
+        }
+        let (mut min, mut max, mut sum_squares) = (i16::MAX, i16::MIN, 0.0f64);
+        let mut add = |peak: &Peak, frames: usize| {
+            min = min.min(peak.min);
+            max = max.max(peak.max);
+            sum_squares += peak.mean_square as f64 * frames as f64;
+        };
+
+        // Whole blocks `first..last` at level 0, with the partial blocks around them scanned;
+        // each level up halves the indices, taking the odd block out at either end
+        let (mut first, mut last) = (from.div_ceil(BASE_BLOCK), to / BASE_BLOCK);
+        let (head_end, tail_start) = if first < last { (first * BASE_BLOCK, last * BASE_BLOCK) } else { (to, to) };
+        for (level, peaks) in levels.iter().enumerate() {
+            if first >= last {
+                break;
+            }
+            if first % 2 == 1 {
+                add(&peaks[first], BASE_BLOCK << level);
+                first += 1;
+            }
+            if last % 2 == 1 && first < last {
+                last -= 1;
+                add(&peaks[last], BASE_BLOCK << level);
+            }
+            (first, last) = (first / 2, last / 2);
+        }
+        for frame in (from..head_end).chain(tail_start..to) {
+            let s = frame_value(frame);
+            add(&Peak { min: s, max: s, mean_square: (s as f32) * (s as f32) }, 1);
+        }
+        Some(Peak { min, max, mean_square: (sum_squares / (to - from) as f64) as f32 })
+    }
+
+    /// Path of the sidecar cache for a WAV file, e.g. `take1.wav.peaks`.
+    pub fn sidecar_path(wav_path: &Path) -> PathBuf {
+        let mut path = wav_path.as_os_str().to_owned();
+        path.push(".peaks");
+        PathBuf::from(path)
+    }
+
+    /// Loads a sidecar written by `save`, if it is still valid for `wav_path` and describes
+    /// `frames` frames of `channels` channels. A corrupt or mismatched file is an error, so the
+    /// caller can rebuild the pyramid as if there were no cache.
+    pub fn load(wav_path: &Path, frames: usize, channels: usize) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        let file = File::open(Self::sidecar_path(wav_path))?;
+        let mut remaining = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let mut header = [0u8; 4];
+        reader.read_exact(&mut header)?;
+        if &header != SIDECAR_MAGIC || read_u32(&mut reader)? != SIDECAR_VERSION {
+            return Err(invalid("Unknown peak file format"));
+        }
+        let (size, modified) = source_stamp(wav_path)?;
+        if read_u64(&mut reader)? != size || read_u64(&mut reader)? != modified {
+            return Err(invalid("Peak file is out of date"));
+        }
+
+        // Every count must match what `build` produces for this file, so a damaged sidecar
+        // can neither request a huge allocation nor produce levels `range` can't index
+        let len = read_u64(&mut reader)? as usize;
+        let lane_count = if channels > 1 { channels + 1 } else { 1 };
+        if len != frames || read_u32(&mut reader)? as usize != channels || read_u32(&mut reader)? as usize != lane_count {
+            return Err(invalid("Peak file doesn't match the audio"));
+        }
+        let expected_levels = level_lengths(frames);
+        remaining = remaining.saturating_sub(4 + 4 + 8 + 8 + 8 + 4 + 4);
+
+        let mut lanes = Vec::with_capacity(lane_count);
+        for _ in 0..lane_count {
+            if read_u32(&mut reader)? as usize != expected_levels.len() {
+                return Err(invalid("Peak file doesn't match the audio"));
+            }
+            remaining = remaining.saturating_sub(4);
+            let mut levels = Vec::with_capacity(expected_levels.len());
+            for &expected in &expected_levels {
+                let count = read_u64(&mut reader)?;
+                remaining = remaining.saturating_sub(8);
+                if count != expected as u64 || count.saturating_mul(8) > remaining {
+                    return Err(invalid("Peak file is truncated or doesn't match the audio"));
+                }
+                remaining -= count * 8;
+                let mut level = Vec::with_capacity(expected);
+                let mut buf = [0u8; 8];
+                for _ in 0..expected {
+                    reader.read_exact(&mut buf)?;
+                    level.push(Peak {
+                        min: i16::from_le_bytes([buf[0], buf[1]]),
+                        max: i16::from_le_bytes([buf[2], buf[3]]),
+                        mean_square: f32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]),
+                    });
+                }
+                levels.push(level);
+            }
+            lanes.push(levels);
+        }
+        Ok(Self { lanes, channels, len })
+    }
+
+    /// Writes the pyramid next to `wav_path`, stamped with the WAV file's size and
+    /// modification time so a changed file is not drawn with stale peaks.
+    pub fn save(&self, wav_path: &Path) -> io::Result<()> {
+        let (size, modified) = source_stamp(wav_path)?;
+        let mut writer = BufWriter::new(File::create(Self::sidecar_path(wav_path))?);
+        writer.write_all(SIDECAR_MAGIC)?;
+        writer.write_all(&SIDECAR_VERSION.to_le_bytes())?;
+        writer.write_all(&size.to_le_bytes())?;
+        writer.write_all(&modified.to_le_bytes())?;
+        writer.write_all(&(self.len as u64).to_le_bytes())?;
+        writer.write_all(&(self.channels as u32).to_le_bytes())?;
+        writer.write_all(&(self.lanes.len() as u32).to_le_bytes())?;
+        for levels in &self.lanes {
+            writer.write_all(&(levels.len() as u32).to_le_bytes())?;
+            for level in levels {
+                writer.write_all(&(level.len() as u64).to_le_bytes())?;
+                for peak in level {
+                    writer.write_all(&peak.min.to_le_bytes())?;
+                    writer.write_all(&peak.max.to_le_bytes())?;
+                    writer.write_all(&peak.mean_square.to_le_bytes())?;
+                }
+            }
+        }
+        writer.flush()
+    }
+}
+
+/// Number of peaks in each level `build` produces for `frames` frames.
+fn level_lengths(frames: usize) -> Vec<usize> {
+    let mut lengths = vec![frames.div_ceil(BASE_BLOCK)];
+    while let Some(&last) = lengths.last().filter(|&&len| len > 1) {
+        lengths.push(last.div_ceil(2));
+    }
+    lengths
+}
+
+/// File size and modification time in nanoseconds since the epoch.
+fn source_stamp(path: &Path) -> io::Result<(u64, u64)> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    Ok((metadata.len(), modified))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
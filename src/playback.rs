@@ -0,0 +1,449 @@
+use crate::audio::{build_stream, negotiate_config, output_device, PlaybackSource};
+use crate::resample::Resampler;
+use crate::wav::SampleBuffer;
+use cpal::traits::{DeviceTrait, StreamTrait};
+use hound::WavSpec;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::sync::Arc;
+
+/// Commands queued between two callbacks. The queue is bounded, so sending never allocates
+/// on either side.
+const COMMAND_QUEUE_LEN: usize = 64;
+
+/// `Shared::seek` when no seek is pending.
+const NO_SEEK: usize = usize::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransportState {
+    Stopped,
+    Playing,
+    Paused,
+}
+
+/// Sent from the UI to the audio callback.
+enum Command {
+    /// Replaces the playing buffer, continuing at interleaved sample `position` of `range`.
+    Load {
+        samples: Arc<SampleBuffer>,
+        range: Range<usize>,
+        position: usize,
+    },
+    Play,
+    Pause,
+}
+
+/// Written by the audio callback, read by the UI, except `seek`: the latest position the UI
+/// asked for, or `NO_SEEK`. Seeks replace each other instead of queueing, so dragging the
+/// playhead while the device is paused can't fill the command queue.
+struct Shared {
+    position: AtomicUsize,
+    finished: AtomicBool,
+    seek: AtomicUsize,
+}
+
+/// UI side of a renderer: queues commands for the audio callback and reads back its position
+/// without ever waiting for it.
+pub struct TransportControl {
+    commands: SyncSender<Command>,
+    retired: Receiver<Arc<SampleBuffer>>,
+    shared: Arc<Shared>,
+}
+
+impl TransportControl {
+    fn send(&self, command: Command) {
+        // Only loads, plays and pauses queue, and the callback drains them every buffer, so it
+        // only fills up if the device stalls or the user clicks faster than it calls back
+        if self.commands.try_send(command).is_err() {
+            eprintln!("Playback command queue is full, dropping command");
+        }
+    }
+
+    /// Plays `range` of `samples` from interleaved sample `position` from the next buffer on,
+    /// keeping the play/pause state.
+    pub fn load(&self, samples: Arc<SampleBuffer>, range: Range<usize>, position: usize) {
+        // The load carries its own position, which an earlier seek must not override
+        self.shared.seek.store(NO_SEEK, Ordering::Relaxed);
+        self.shared.position.store(position, Ordering::Relaxed);
+        self.send(Command::Load { samples, range, position });
+    }
+
+    pub fn seek(&self, position: usize) {
+        self.shared.position.store(position, Ordering::Relaxed);
+        self.shared.seek.store(position, Ordering::Release);
+    }
+
+    pub fn play(&self) {
+        self.shared.finished.store(false, Ordering::Relaxed);
+        self.send(Command::Play);
+    }
+
+    pub fn pause(&self) {
+        self.send(Command::Pause);
+    }
+
+    /// Loads into a renderer that replaces another, e.g. on a different device, and keeps
+    /// playing if the old one was.
+    pub fn resume(&self, samples: Arc<SampleBuffer>, range: Range<usize>, position: usize, playing: bool) {
+        self.load(samples, range, position);
+        if playing {
+            self.play();
+        }
+    }
+
+    /// Interleaved index of the next sample to be played.
+    pub fn position(&self) -> usize {
+        self.shared.position.load(Ordering::Relaxed)
+    }
+
+    /// Returns true once after playback has reached the end of its range.
+    pub fn take_finished(&self) -> bool {
+        self.shared.finished.swap(false, Ordering::Acquire)
+    }
+
+    /// Frees buffers the callback has stopped using. Dropping them in the callback could free
+    /// a whole file's memory on the audio thread.
+    pub fn collect_retired(&self) {
+        while self.retired.try_recv().is_ok() {}
+    }
+}
+
+/// Produces the device's output. `render` only does arithmetic, atomic stores and
+/// non-blocking queue operations: it never locks, allocates or frees, so it is safe to call
+/// from the real-time audio callback.
+pub struct Renderer {
+    commands: Receiver<Command>,
+    retired: SyncSender<Arc<SampleBuffer>>,
+    // A replaced buffer the retired queue had no room for, handed back on a later callback
+    retiring: Option<Arc<SampleBuffer>>,
+    shared: Arc<Shared>,
+    samples: Option<Arc<SampleBuffer>>,
+    // Playable frames of `samples`
+    range: Range<usize>,
+    playing: bool,
+    file_channels: usize,
+    device_channels: usize,
+    resampler: Option<Resampler>,
+    step: f64,
+    // Playback position in file frames; fractional while resampling
+    position: f64,
+    frame: Vec<f32>,
+}
+
+/// Creates a connected control and renderer for files with the layout of `spec`, played on a
+/// device with `device_channels` channels running at `device_rate`. The renderer resamples if
+/// the rates differ. It starts stopped with nothing loaded.
+pub fn transport_channel(spec: WavSpec, device_channels: usize, device_rate: u32) -> (TransportControl, Renderer) {
+    let (command_tx, command_rx) = mpsc::sync_channel(COMMAND_QUEUE_LEN);
+    let (retired_tx, retired_rx) = mpsc::sync_channel(COMMAND_QUEUE_LEN);
+    let shared = Arc::new(Shared {
+        position: AtomicUsize::new(0),
+        finished: AtomicBool::new(false),
+        seek: AtomicUsize::new(NO_SEEK),
+    });
+    let resampler = (device_rate != spec.sample_rate).then(|| Resampler::new(spec.sample_rate, device_rate));
+    let control = TransportControl {
+        commands: command_tx,
+        retired: retired_rx,
+        shared: Arc::clone(&shared),
+    };
+    let renderer = Renderer {
+        commands: command_rx,
+        retired: retired_tx,
+        retiring: None,
+        shared,
+        samples: None,
+        range: 0..0,
+        playing: false,
+        file_channels: spec.channels as usize,
+        device_channels,
+        step: resampler.as_ref().map_or(1.0, |r| r.step()),
+        resampler,
+        position: 0.0,
+        frame: vec![0.0; spec.channels as usize],
+    };
+    (control, renderer)
+}
+
+impl Renderer {
+    /// Hands `buffer` back to the UI, or keeps it until the retired queue has room.
+    fn retire(&mut self, buffer: Arc<SampleBuffer>) {
+        if let Err(TrySendError::Full(buffer) | TrySendError::Disconnected(buffer)) = self.retired.try_send(buffer) {
+            self.retiring = Some(buffer);
+        }
+    }
+
+    fn apply_commands(&mut self) {
+        if let Some(buffer) = self.retiring.take() {
+            self.retire(buffer);
+        }
+        // While a replaced buffer is waiting to be handed back, further loads wait in the queue
+        let drained = loop {
+            if self.retiring.is_some() {
+                break false;
+            }
+            match self.commands.try_recv() {
+                Ok(Command::Load { samples, range, position }) => {
+                    self.range = range.start / self.file_channels..range.end.min(samples.len()) / self.file_channels;
+                    self.position = (position / self.file_channels) as f64;
+                    if let Some(old) = self.samples.replace(samples) {
+                        self.retire(old);
+                    }
+                }
+                Ok(Command::Play) => {
+                    // Playing again after reaching the end starts over
+                    if self.position >= self.range.end as f64 {
+                        self.position = self.range.start as f64;
+                    }
+                    self.playing = true;
+                }
+                Ok(Command::Pause) => self.playing = false,
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => break true,
+            }
+        };
+        // A seek applies to the last load, so it waits until every queued load is applied
+        if drained {
+            let seek = self.shared.seek.swap(NO_SEEK, Ordering::Acquire);
+            if seek != NO_SEEK {
+                self.position = (seek / self.file_channels) as f64;
+            }
+        }
+        self.position = self.position.clamp(self.range.start as f64, self.range.end as f64);
+    }
+
+    /// Fills `out`, interleaved with `device_channels` channels, and advances the position.
+    pub fn render(&mut self, out: &mut [f32]) {
+        self.apply_commands();
+        let Some(samples) = self.samples.as_ref().filter(|_| self.playing) else {
+            out.fill(0.0);
+            return;
+        };
+
+        let end = self.range.end as f64;
+        for out in out.chunks_mut(self.device_channels) {
+            if self.position >= end {
+                out.fill(0.0);
+                continue;
+            }
+            match &self.resampler {
+                Some(resampler) => {
+                    // Frames outside the range are not played, so don't let them ring into it
+                    let start = self.range.start * self.file_channels;
+                    let range = &samples[start..self.range.end * self.file_channels];
+                    resampler.frame_at(range, self.file_channels, self.position - self.range.start as f64, &mut self.frame);
+                }
+                None => {
+                    let start = self.position as usize * self.file_channels;
+                    for (value, &sample) in self.frame.iter_mut().zip(&samples[start..start + self.file_channels]) {
+                        *value = sample as f32 / i16::MAX as f32;
+                    }
+                }
+            }
+            map_channels(&self.frame, out);
+            self.position += self.step;
+        }
+
+        let played = (self.position as usize).min(self.range.end) * self.file_channels;
+        self.shared.position.store(played, Ordering::Relaxed);
+        if self.position >= end {
+            self.playing = false;
+            self.shared.finished.store(true, Ordering::Release);
+        }
+    }
+}
+
+/// Copies one file frame to one device frame, duplicating mono to every output channel,
+/// averaging to a mono output, and otherwise dropping or silencing extra channels.
+fn map_channels(input: &[f32], output: &mut [f32]) {
+    if input.len() == output.len() {
+        output.copy_from_slice(input);
+    } else if input.len() == 1 {
+        output.fill(input[0]);
+    } else if output.len() == 1 {
+        output[0] = input.iter().sum::<f32>() / input.len() as f32;
+    } else {
+        for (c, sample) in output.iter_mut().enumerate() {
+            *sample = input.get(c).copied().unwrap_or(0.0);
+        }
+    }
+}
+
+/// Single output stream shared by every playback source. Switching between sources only
+/// changes what the open stream's callback reads, so it takes effect at the next buffer
+/// without reopening the device.
+pub struct Transport {
+    output: Option<Output>,
+    state: TransportState,
+    source: Option<PlaybackSource>,
+    range: Range<usize>,
+}
+
+struct Output {
+    stream: cpal::Stream,
+    control: TransportControl,
+    errors: Receiver<String>,
+    device_name: Option<String>,
+    spec: WavSpec,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport {
+    pub fn new() -> Self {
+        Self {
+            output: None,
+            state: TransportState::Stopped,
+            source: None,
+            range: 0..0,
+        }
+    }
+
+    pub fn state(&self) -> TransportState {
+        self.state
+    }
+
+    /// The waveform currently loaded, playing or not.
+    pub fn source(&self) -> Option<PlaybackSource> {
+        self.source
+    }
+
+    /// The part of the loaded waveform that plays, in interleaved samples.
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// Interleaved sample index within the loaded waveform.
+    pub fn position(&self) -> usize {
+        self.output.as_ref().map_or(self.range.start, |output| output.control.position())
+    }
+
+    /// Opens the output device, unless a stream for the same device and file layout is open.
+    /// Also returns whether a new stream was opened; its renderer starts out stopped.
+    fn open(&mut self, device_name: Option<&str>, spec: WavSpec) -> Result<(&TransportControl, bool), String> {
+        let reuse = self
+            .output
+            .as_ref()
+            .is_some_and(|output| output.device_name.as_deref() == device_name && output.spec == spec);
+        if !reuse {
+            self.output = None;
+            self.state = TransportState::Stopped;
+            let device = output_device(device_name)?;
+            let supported = negotiate_config(&device, spec)?;
+            let config = supported.config();
+            println!(
+                "Playing on {:?}: {} channels, {} Hz, {}",
+                device.name().unwrap_or_default(),
+                config.channels,
+                config.sample_rate.0,
+                supported.sample_format()
+            );
+
+            let (control, mut renderer) = transport_channel(spec, config.channels as usize, config.sample_rate.0);
+            let render = move |data: &mut [f32]| renderer.render(data);
+            let (error_tx, errors) = mpsc::channel();
+            let stream = match supported.sample_format() {
+                cpal::SampleFormat::I16 => build_stream::<i16, _>(&device, &config, render, error_tx)?,
+                cpal::SampleFormat::I32 => build_stream::<i32, _>(&device, &config, render, error_tx)?,
+                cpal::SampleFormat::U16 => build_stream::<u16, _>(&device, &config, render, error_tx)?,
+                _ => build_stream::<f32, _>(&device, &config, render, error_tx)?,
+            };
+            self.output = Some(Output {
+                stream,
+                control,
+                errors,
+                device_name: device_name.map(String::from),
+                spec,
+            });
+        }
+        let output = self.output.as_ref().unwrap();
+        output.stream.play().map_err(|err| format!("Failed to start playback: {}", err))?;
+        Ok((&output.control, !reuse))
+    }
+
+    /// Switches to `range` of `samples`, continuing at `position`, from the next buffer on.
+    /// Keeps playing if playing, otherwise cues the source for `play`.
+    pub fn load(
+        &mut self,
+        source: PlaybackSource,
+        samples: Arc<SampleBuffer>,
+        range: Range<usize>,
+        position: usize,
+        spec: WavSpec,
+        device_name: Option<&str>,
+    ) -> Result<(), String> {
+        let position = position.clamp(range.start, range.end);
+        let state = self.state;
+        let (control, reopened) = self.open(device_name, spec)?;
+        if reopened {
+            // The new renderer knows nothing of the old one, so tell it to carry on playing
+            control.resume(samples, range.clone(), position, state == TransportState::Playing);
+            self.state = state;
+        } else {
+            control.load(samples, range.clone(), position);
+        }
+        self.source = Some(source);
+        self.range = range;
+        Ok(())
+    }
+
+    /// Plays the loaded source from its current position.
+    pub fn play(&mut self) -> Result<(), String> {
+        if let (Some(output), Some(_)) = (&self.output, self.source) {
+            output.stream.play().map_err(|err| format!("Failed to start playback: {}", err))?;
+            output.control.play();
+            self.state = TransportState::Playing;
+        }
+        Ok(())
+    }
+
+    pub fn pause(&mut self) {
+        if let (Some(output), TransportState::Playing) = (&self.output, self.state) {
+            output.control.pause();
+            self.state = TransportState::Paused;
+        }
+    }
+
+    /// Stops and rewinds to the start of the loaded range.
+    pub fn stop(&mut self) {
+        if let Some(output) = &self.output {
+            output.control.pause();
+            output.control.seek(self.range.start);
+            // Nothing plays while stopped, so the device can rest
+            let _ = output.stream.pause();
+        }
+        self.state = TransportState::Stopped;
+    }
+
+    pub fn seek(&mut self, position: usize) {
+        if let Some(output) = &self.output {
+            output.control.seek(position.clamp(self.range.start, self.range.end));
+        }
+    }
+
+    /// Forgets the loaded source, e.g. when its samples are replaced.
+    pub fn unload(&mut self) {
+        self.stop();
+        self.source = None;
+    }
+
+    /// Picks up the end of playback and returns the latest error reported by the stream, after
+    /// which the stream is closed and the next `load` opens the device again. Call once per UI frame.
+    pub fn update(&mut self) -> Option<String> {
+        let output = self.output.as_ref()?;
+        output.control.collect_retired();
+        if output.control.take_finished() && self.state == TransportState::Playing {
+            self.state = TransportState::Stopped;
+            println!("Playback finished");
+        }
+        let error = output.errors.try_iter().last()?;
+        self.output = None;
+        self.state = TransportState::Stopped;
+        self.source = None;
+        Some(error)
+    }
+}
@@ -0,0 +1,121 @@
+use crate::job::CancelToken;
+
+/// Zero crossings of the sinc kernel on each side of the interpolated position, at the output
+/// rate when downsampling. More gives a steeper low-pass filter at a higher cost.
+const ZERO_CROSSINGS: usize = 16;
+
+/// Kernel values stored per input frame; positions in between are linearly interpolated.
+const PHASES: usize = 512;
+
+/// Kaiser window shape; 8.0 keeps the stopband below -80 dB.
+const KAISER_BETA: f64 = 8.0;
+
+/// Band-limited sample rate converter using a Kaiser-windowed sinc kernel. The kernel is
+/// tabulated once in `new`, so interpolating a frame does not allocate and is safe to call
+/// from the audio callback.
+pub struct Resampler {
+    step: f64,
+    half_width: usize,
+    table: Vec<f32>,
+}
+
+impl Resampler {
+    pub fn new(from_rate: u32, to_rate: u32) -> Self {
+        // Downsampling lowers the cutoff to the new Nyquist frequency to avoid aliasing
+        let cutoff = (to_rate as f64 / from_rate as f64).min(1.0);
+        let half_width = (ZERO_CROSSINGS as f64 / cutoff).ceil() as usize;
+        let table = (0..=2 * half_width * PHASES)
+            .map(|i| {
+                let x = i as f64 / PHASES as f64 - half_width as f64;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    let t = std::f64::consts::PI * cutoff * x;
+                    t.sin() / t
+                };
+                let w = x / half_width as f64;
+                let window = bessel_i0(KAISER_BETA * (1.0 - w * w).max(0.0).sqrt()) / bessel_i0(KAISER_BETA);
+                (cutoff * sinc * window) as f32
+            })
+            .collect();
+        Self {
+            step: from_rate as f64 / to_rate as f64,
+            half_width,
+            table,
+        }
+    }
+
+    /// Input frames to advance per output frame.
+    pub fn step(&self) -> f64 {
+        self.step
+    }
+
+    /// Interpolates the frame at fractional input frame `position` of interleaved `samples`,
+    /// writing one value per channel to `out` in -1.0..=1.0. Frames outside the input are silent.
+    pub fn frame_at(&self, samples: &[i16], channels: usize, position: f64, out: &mut [f32]) {
+        out.fill(0.0);
+        let frames = samples.len() / channels;
+        let center = position.floor() as i64;
+        let first = center - self.half_width as i64 + 1;
+        for frame in first.max(0)..(center + self.half_width as i64 + 1).min(frames as i64) {
+            let x = (frame as f64 - position + self.half_width as f64) * PHASES as f64;
+            let i = x as usize;
+            let t = (x - i as f64) as f32;
+            let next = self.table.get(i + 1).copied().unwrap_or(0.0);
+            let weight = self.table[i] + (next - self.table[i]) * t;
+            let frame = &samples[frame as usize * channels..(frame as usize + 1) * channels];
+            for (value, &sample) in out.iter_mut().zip(frame) {
+                *value += weight * sample as f32 / i16::MAX as f32;
+            }
+        }
+    }
+}
+
+/// Output frames converted between progress reports and cancellation checks.
+const PROGRESS_INTERVAL: usize = 1 << 14;
+
+/// Converts interleaved samples from one sample rate to another. Returns `None` if cancelled.
+pub fn resample<F: FnMut(f32)>(
+    samples: &[i16],
+    channels: usize,
+    from_rate: u32,
+    to_rate: u32,
+    cancel: &CancelToken,
+    mut on_progress: F,
+) -> Option<Vec<i16>> {
+    if from_rate == to_rate {
+        return Some(samples.to_vec());
+    }
+    let resampler = Resampler::new(from_rate, to_rate);
+    let frames = samples.len() / channels;
+    let out_frames = (frames as u64 * to_rate as u64).div_ceil(from_rate as u64) as usize;
+    let mut output = Vec::with_capacity(out_frames * channels);
+    let mut frame = vec![0.0f32; channels];
+    for i in 0..out_frames {
+        if i % PROGRESS_INTERVAL == 0 {
+            if cancel.is_cancelled() {
+                return None;
+            }
+            on_progress(i as f32 / out_frames as f32);
+        }
+        resampler.frame_at(samples, channels, i as f64 * resampler.step(), &mut frame);
+        output.extend(frame.iter().map(|&v| (v * i16::MAX as f32).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16));
+    }
+    on_progress(1.0);
+    Some(output)
+}
+
+/// Zeroth-order modified Bessel function of the first kind, for the Kaiser window.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = x / 2.0;
+    for k in 1..50 {
+        term *= (half / k as f64) * (half / k as f64);
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
@@ -0,0 +1,161 @@
+use crate::job::CancelToken;
+use eframe::egui::{Color32, ColorImage};
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use std::ops::Range;
+
+/// Largest spectrogram image, in columns (time) and rows (frequency). Kept within the texture
+/// size every egui backend supports; when zoomed in, the visible range is computed separately.
+pub const MAX_COLUMNS: usize = 2048;
+const MAX_ROWS: usize = 512;
+
+/// Magnitudes below this level are drawn with the lowest color.
+const MIN_DB: f32 = -100.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisplayMode {
+    Waveform,
+    Spectrogram,
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowFunction {
+    Hann,
+    Hamming,
+    Blackman,
+    Rectangular,
+}
+
+impl WindowFunction {
+    pub const ALL: [WindowFunction; 4] = [
+        WindowFunction::Hann,
+        WindowFunction::Hamming,
+        WindowFunction::Blackman,
+        WindowFunction::Rectangular,
+    ];
+
+    fn coefficients(&self, size: usize) -> Vec<f32> {
+        let n = (size - 1).max(1) as f32;
+        (0..size)
+            .map(|i| {
+                let phase = std::f32::consts::TAU * i as f32 / n;
+                match self {
+                    WindowFunction::Hann => 0.5 - 0.5 * phase.cos(),
+                    WindowFunction::Hamming => 0.54 - 0.46 * phase.cos(),
+                    WindowFunction::Blackman => 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos(),
+                    WindowFunction::Rectangular => 1.0,
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorMap {
+    Grayscale,
+    Heat,
+    Viridis,
+}
+
+impl ColorMap {
+    pub const ALL: [ColorMap; 3] = [ColorMap::Grayscale, ColorMap::Heat, ColorMap::Viridis];
+
+    /// Maps a level in 0.0..=1.0 to a color by interpolating between gradient stops.
+    pub fn color(&self, level: f32) -> Color32 {
+        let stops: &[(u8, u8, u8)] = match self {
+            ColorMap::Grayscale => &[(255, 255, 255), (0, 0, 0)],
+            ColorMap::Heat => &[(0, 0, 0), (128, 0, 0), (255, 96, 0), (255, 220, 0), (255, 255, 255)],
+            ColorMap::Viridis => &[(68, 1, 84), (59, 82, 139), (33, 145, 140), (94, 201, 98), (253, 231, 37)],
+        };
+        let scaled = level.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let i = (scaled as usize).min(stops.len() - 2);
+        let t = scaled - i as f32;
+        let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t) as u8;
+        let (a, b) = (stops[i], stops[i + 1]);
+        Color32::from_rgb(lerp(a.0, b.0), lerp(a.1, b.1), lerp(a.2, b.2))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectrogramSettings {
+    pub fft_size: usize,
+    pub window: WindowFunction,
+    pub color_map: ColorMap,
+}
+
+impl Default for SpectrogramSettings {
+    fn default() -> Self {
+        Self {
+            fft_size: 1024,
+            window: WindowFunction::Hann,
+            color_map: ColorMap::Heat,
+        }
+    }
+}
+
+/// Columns for a spectrogram of the whole file: one per quarter window, up to `MAX_COLUMNS`.
+pub fn overview_columns(total_frames: usize, fft_size: usize) -> usize {
+    total_frames.div_ceil((fft_size / 4).max(1)).clamp(1, MAX_COLUMNS)
+}
+
+/// Computes a short-time Fourier transform of `frames`, mixed down to mono, as an image with
+/// time left to right and frequency bottom to top. The range is split evenly into `columns`
+/// columns (at most `MAX_COLUMNS`, and no more than there are frames), and each column's window
+/// is centred on the middle of its part. Returns `None` if cancelled.
+pub fn compute_spectrogram<F: FnMut(f32)>(
+    samples: &[i16],
+    channels: usize,
+    settings: SpectrogramSettings,
+    frames: Range<usize>,
+    columns: usize,
+    cancel: &CancelToken,
+    mut on_progress: F,
+) -> Option<ColorImage> {
+    let fft_size = settings.fft_size;
+    let total_frames = samples.len() / channels;
+    let columns = columns.min(MAX_COLUMNS).min(frames.len()).max(1);
+    let hop = frames.len() as f64 / columns as f64;
+    let bins = fft_size / 2;
+    let rows = bins.min(MAX_ROWS);
+
+    let window = settings.window.coefficients(fft_size);
+    let fft = FftPlanner::<f32>::new().plan_fft_forward(fft_size);
+    let mut buffer = vec![Complex::new(0.0, 0.0); fft_size];
+    let mut image = ColorImage::new([columns, rows], Color32::BLACK);
+    let norm = window.iter().sum::<f32>() / 2.0 * i16::MAX as f32;
+
+    for column in 0..columns {
+        if column % 64 == 0 {
+            if cancel.is_cancelled() {
+                return None;
+            }
+            on_progress(column as f32 / columns as f32);
+        }
+
+        let center = frames.start as f64 + (column as f64 + 0.5) * hop;
+        let start = center as i64 - (fft_size / 2) as i64;
+        for (i, value) in buffer.iter_mut().enumerate() {
+            let frame = start + i as i64;
+            let mono = if (0..total_frames as i64).contains(&frame) {
+                let frame = &samples[frame as usize * channels..(frame as usize + 1) * channels];
+                frame.iter().map(|&s| s as f32).sum::<f32>() / channels as f32
+            } else {
+                0.0
+            };
+            *value = Complex::new(mono * window[i], 0.0);
+        }
+        fft.process(&mut buffer);
+
+        // Several FFT bins share a row when there are more bins than rows; keep the loudest
+        for row in 0..rows {
+            let (from, to) = (row * bins / rows, ((row + 1) * bins / rows).max(row * bins / rows + 1));
+            let magnitude = buffer[from..to].iter().map(|c| c.norm()).fold(0.0, f32::max) / norm;
+            let db = 20.0 * magnitude.max(1e-10).log10();
+            let level = (db - MIN_DB) / -MIN_DB;
+            image[(column, rows - 1 - row)] = settings.color_map.color(level);
+        }
+    }
+    on_progress(1.0);
+    Some(image)
+}
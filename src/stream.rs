@@ -0,0 +1,109 @@
+use crate::audio::SilenceDetector;
+use crate::job::CancelToken;
+use hound::{WavReader, WavWriter};
+use std::io;
+use std::path::Path;
+
+/// Number of frames read from disk at a time.
+const CHUNK_FRAMES: usize = 64 * 1024;
+
+pub struct StreamSummary {
+    pub silence_segments: Vec<(usize, usize)>,
+    pub samples_read: usize,
+    pub samples_written: usize,
+}
+
+/// Removes silence from `input` and writes the result to `output` without loading the whole file.
+///
+/// Memory use is bounded by one read chunk plus at most `min_silence_len` of buffered silence:
+/// a silent run is held back until it is either long enough to be dropped or ends early and is
+/// written out unchanged. Cancelling stops after the current chunk with an `Interrupted` error
+/// and deletes the partially written output file.
+pub fn remove_silence_streaming<F: FnMut(f32)>(
+    input: &Path,
+    output: &Path,
+    threshold: f32,
+    min_silence_len: usize,
+    cancel: &CancelToken,
+    mut on_progress: F,
+) -> Result<StreamSummary, hound::Error> {
+    let mut reader = WavReader::open(input)?;
+    let spec = reader.spec();
+    let channels = spec.channels as usize;
+    let total_samples = reader.len() as usize;
+    let mut writer = WavWriter::create(output, spec)?;
+    let mut detector = SilenceDetector::new(threshold, min_silence_len, spec);
+
+    let mut silence_segments = Vec::new();
+    let mut pending: Vec<i16> = Vec::new();
+    let mut chunk: Vec<i16> = Vec::with_capacity(CHUNK_FRAMES * channels);
+    let mut samples = reader.samples::<i16>();
+    let mut samples_read = 0;
+    let mut samples_written = 0;
+
+    loop {
+        if cancel.is_cancelled() {
+            // A truncated file would look like a valid result, so don't leave one behind
+            drop(writer);
+            let _ = std::fs::remove_file(output);
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled").into());
+        }
+        chunk.clear();
+        for sample in samples.by_ref().take(CHUNK_FRAMES * channels) {
+            chunk.push(sample?);
+        }
+        if chunk.is_empty() {
+            break;
+        }
+
+        for frame in chunk.chunks(channels) {
+            let amplitude = SilenceDetector::frame_amplitude(frame, channels);
+            let ended = detector.push(samples_read, amplitude);
+            samples_read += frame.len();
+
+            if let Some(segment) = ended {
+                silence_segments.push(segment);
+            }
+            if detector.silence_frames() > 0 {
+                // Hold silence back until we know whether it is long enough to be removed
+                if detector.silence_frames() < detector.min_frames() {
+                    pending.extend_from_slice(frame);
+                } else {
+                    pending.clear();
+                }
+                continue;
+            }
+            // A loud frame: a held-back run that was too short to remove is written out
+            if ended.is_none() {
+                for &sample in &pending {
+                    writer.write_sample(sample)?;
+                }
+                samples_written += pending.len();
+            }
+            pending.clear();
+            for &sample in frame {
+                writer.write_sample(sample)?;
+            }
+            samples_written += frame.len();
+        }
+
+        on_progress(samples_read as f32 / total_samples.max(1) as f32);
+    }
+
+    match detector.finish(samples_read) {
+        Some(segment) => silence_segments.push(segment),
+        None => {
+            for &sample in &pending {
+                writer.write_sample(sample)?;
+            }
+            samples_written += pending.len();
+        }
+    }
+    writer.finalize()?;
+
+    Ok(StreamSummary {
+        silence_segments,
+        samples_read,
+        samples_written,
+    })
+}
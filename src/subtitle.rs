@@ -0,0 +1,201 @@
+use crate::timeline::TimelineMap;
+use hound::WavSpec;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+impl SubtitleFormat {
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "srt" => Some(SubtitleFormat::Srt),
+            "vtt" => Some(SubtitleFormat::Vtt),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Vtt => "vtt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    /// NOTE, STYLE or other blocks between the previous cue and this one, kept verbatim.
+    pub blocks: Vec<String>,
+    /// SRT sequence number or WebVTT cue identifier.
+    pub identifier: Option<String>,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// WebVTT cue settings following the timing, e.g. `align:start`.
+    pub settings: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subtitles {
+    pub format: SubtitleFormat,
+    /// Blocks before the first cue (the WEBVTT line, STYLE and NOTE blocks), kept verbatim.
+    pub header: Vec<String>,
+    pub cues: Vec<Cue>,
+    /// Blocks after the last cue, kept verbatim.
+    pub trailer: Vec<String>,
+}
+
+impl Subtitles {
+    pub fn parse(content: &str, format: SubtitleFormat) -> Result<Self, String> {
+        let content = content.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+        let mut header = Vec::new();
+        let mut cues = Vec::new();
+        let mut blocks = Vec::new();
+
+        for block in content.split("\n\n").map(|b| b.trim_matches('\n')).filter(|b| !b.is_empty()) {
+            let lines: Vec<&str> = block.lines().collect();
+            let Some(timing_pos) = lines.iter().position(|l| l.contains("-->")) else {
+                if cues.is_empty() {
+                    header.push(block.to_string());
+                } else {
+                    blocks.push(block.to_string());
+                }
+                continue;
+            };
+
+            let identifier = match timing_pos {
+                0 => None,
+                1 => Some(lines[0].trim().to_string()),
+                _ => return Err(format!("Unexpected text before cue timing: {:?}", lines[0])),
+            };
+            let (timing, settings) = lines[timing_pos]
+                .split_once("-->")
+                .map(|(start, rest)| {
+                    let rest = rest.trim();
+                    let (end, settings) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    ((start.trim(), end), settings.trim())
+                })
+                .unwrap();
+
+            cues.push(Cue {
+                blocks: std::mem::take(&mut blocks),
+                identifier,
+                start_ms: parse_timestamp(timing.0)?,
+                end_ms: parse_timestamp(timing.1)?,
+                settings: settings.to_string(),
+                text: lines[timing_pos + 1..].join("\n"),
+            });
+        }
+
+        if format == SubtitleFormat::Vtt && !header.first().is_some_and(|h| h.starts_with("WEBVTT")) {
+            return Err(String::from("Missing WEBVTT header"));
+        }
+
+        Ok(Self {
+            format,
+            header,
+            cues,
+            trailer: blocks,
+        })
+    }
+
+    /// Moves every cue onto the processed timeline. Cues that fall entirely inside removed
+    /// silence are dropped, and cues overlapping a removed segment are trimmed to the cut.
+    pub fn retime(&self, timeline: &TimelineMap, spec: WavSpec) -> Self {
+        let samples_per_ms = spec.sample_rate as f64 * spec.channels as f64 / 1000.0;
+        let to_sample = |ms: u64| (ms as f64 * samples_per_ms) as usize;
+        let to_ms = |idx: usize| (idx as f64 / samples_per_ms).round() as u64;
+
+        // Blocks before a dropped cue stay with the next cue that is kept
+        let mut blocks = Vec::new();
+        let mut cues = Vec::new();
+        for cue in &self.cues {
+            blocks.extend(cue.blocks.iter().cloned());
+            let start = timeline.original_to_processed(to_sample(cue.start_ms));
+            let end = timeline.original_to_processed(to_sample(cue.end_ms));
+            if end > start {
+                cues.push(Cue {
+                    blocks: std::mem::take(&mut blocks),
+                    start_ms: to_ms(start),
+                    end_ms: to_ms(end),
+                    ..cue.clone()
+                });
+            }
+        }
+        blocks.extend(self.trailer.iter().cloned());
+
+        Self {
+            format: self.format,
+            header: self.header.clone(),
+            cues,
+            trailer: blocks,
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for block in &self.header {
+            let _ = write!(out, "{}\n\n", block);
+        }
+        for (i, cue) in self.cues.iter().enumerate() {
+            for block in &cue.blocks {
+                let _ = write!(out, "{}\n\n", block);
+            }
+            match self.format {
+                // SRT sequence numbers must stay consecutive after cues are dropped
+                SubtitleFormat::Srt => {
+                    let _ = writeln!(out, "{}", i + 1);
+                }
+                SubtitleFormat::Vtt => {
+                    if let Some(identifier) = &cue.identifier {
+                        let _ = writeln!(out, "{}", identifier);
+                    }
+                }
+            }
+            let _ = write!(
+                out,
+                "{} --> {}",
+                format_timestamp(cue.start_ms, self.format),
+                format_timestamp(cue.end_ms, self.format)
+            );
+            if !cue.settings.is_empty() {
+                let _ = write!(out, " {}", cue.settings);
+            }
+            let _ = write!(out, "\n{}\n\n", cue.text);
+        }
+        for block in &self.trailer {
+            let _ = write!(out, "{}\n\n", block);
+        }
+        out
+    }
+}
+
+/// Parses `hh:mm:ss,mmm` (SRT) or `[hh:]mm:ss.mmm` (WebVTT) into milliseconds.
+fn parse_timestamp(s: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid timestamp: {:?}", s);
+    let (hms, millis) = s.trim().split_once([',', '.']).ok_or_else(invalid)?;
+    let millis: u64 = millis.parse().map_err(|_| invalid())?;
+    let mut seconds = 0;
+    for part in hms.split(':') {
+        seconds = seconds * 60 + part.parse::<u64>().map_err(|_| invalid())?;
+    }
+    Ok(seconds * 1000 + millis)
+}
+
+fn format_timestamp(ms: u64, format: SubtitleFormat) -> String {
+    let separator = match format {
+        SubtitleFormat::Srt => ',',
+        SubtitleFormat::Vtt => '.',
+    };
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        separator,
+        ms % 1000
+    )
+}
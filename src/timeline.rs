@@ -0,0 +1,101 @@
+use crate::audio::{non_silent_regions, PlaybackSource};
+use std::io::{self, Write};
+
+/// A region of the original audio that was kept, and where it landed in the processed audio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeptRegion {
+    pub processed_start: usize,
+    pub original_start: usize,
+    pub len: usize,
+}
+
+/// Maps sample indices between the original and the processed (silence removed) timelines.
+#[derive(Debug, Clone, Default)]
+pub struct TimelineMap {
+    pub regions: Vec<KeptRegion>,
+    pub original_len: usize,
+}
+
+impl TimelineMap {
+    /// An identity mapping, used while nothing has been removed.
+    pub fn identity(total_samples: usize) -> Self {
+        Self::from_removed(&[], total_samples)
+    }
+
+    pub fn from_removed(removed_segments: &[(usize, usize)], original_len: usize) -> Self {
+        let mut regions = Vec::new();
+        let mut processed_start = 0;
+        for (start, end) in non_silent_regions(removed_segments, original_len) {
+            regions.push(KeptRegion {
+                processed_start,
+                original_start: start,
+                len: end - start,
+            });
+            processed_start += end - start;
+        }
+        Self { regions, original_len }
+    }
+
+    pub fn processed_to_original(&self, processed_idx: usize) -> usize {
+        let i = self.regions.partition_point(|r| r.processed_start <= processed_idx);
+        match i.checked_sub(1).map(|i| &self.regions[i]) {
+            Some(region) if processed_idx < region.processed_start + region.len => {
+                region.original_start + (processed_idx - region.processed_start)
+            }
+            _ => self.original_len,
+        }
+    }
+
+    /// Positions inside removed segments map to the point where playback resumes after the cut.
+    pub fn original_to_processed(&self, original_idx: usize) -> usize {
+        let i = self.regions.partition_point(|r| r.original_start <= original_idx);
+        match i.checked_sub(1).map(|i| &self.regions[i]) {
+            Some(region) if original_idx < region.original_start + region.len => {
+                region.processed_start + (original_idx - region.original_start)
+            }
+            Some(region) => region.processed_start + region.len,
+            None => 0,
+        }
+    }
+
+    /// Maps a position in the `from` waveform to the same moment of the recording in `to`,
+    /// e.g. when switching playback between the original and processed audio.
+    pub fn map_position(&self, from: PlaybackSource, to: PlaybackSource, position: usize) -> usize {
+        match (from, to) {
+            (PlaybackSource::Raw, PlaybackSource::Processed) => self.original_to_processed(position),
+            (PlaybackSource::Processed, PlaybackSource::Raw) => self.processed_to_original(position),
+            _ => position,
+        }
+    }
+
+    /// Returns true when the original sample was removed by silence removal. Indices past the
+    /// end of the file were never there to remove.
+    pub fn is_removed(&self, original_idx: usize) -> bool {
+        if original_idx >= self.original_len {
+            return false;
+        }
+        let i = self.regions.partition_point(|r| r.original_start <= original_idx);
+        match i.checked_sub(1).map(|i| &self.regions[i]) {
+            Some(region) => original_idx >= region.original_start + region.len,
+            None => true,
+        }
+    }
+
+    /// Writes the kept regions as CSV with times in seconds, one row per region.
+    pub fn write_csv<W: Write>(&self, mut writer: W, spec: hound::WavSpec) -> io::Result<()> {
+        let samples_per_second = spec.sample_rate as f64 * spec.channels as f64;
+        let seconds = |idx: usize| idx as f64 / samples_per_second;
+        writeln!(writer, "processed_start,processed_end,original_start,original_end")?;
+        for region in &self.regions {
+            writeln!(
+                writer,
+                "{:.6},{:.6},{:.6},{:.6}",
+                seconds(region.processed_start),
+                seconds(region.processed_start + region.len),
+                seconds(region.original_start),
+                seconds(region.original_start + region.len),
+            )?;
+        }
+        writer.flush()
+    }
+}
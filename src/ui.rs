@@ -1,6 +1,9 @@
-use crate::app::SoundApp;
+use crate::app::{ChannelView, SoundApp, TransportSource, WaveformView};
+use crate::audio::PlaybackSource;
+use crate::job::Progress;
+use crate::peaks::{Peak, PeakPyramid};
+use crate::spectrogram::{ColorMap, DisplayMode, WindowFunction};
 use eframe::egui::{self, Painter, Rect, Sense, Stroke, Color32, Pos2, Align2, FontId, Response};
-use std::sync::Arc;
 
 pub fn draw_ui(app: &mut SoundApp, ctx: &egui::Context) {
     egui::CentralPanel::default().show(ctx, |ui| {
@@ -8,7 +11,8 @@ pub fn draw_ui(app: &mut SoundApp, ctx: &egui::Context) {
 
         ui.vertical(|ui| {
             ui.horizontal(|ui| {
-                if ui.button("Load Audio").clicked() {
+                let load_button = ui.add_enabled(!app.is_loading, egui::Button::new("Load Audio"));
+                if load_button.clicked() {
                     app.load_file();
                 }
                 let detect_button = ui.add_enabled(!app.is_processing, egui::Button::new("Detect Silence"));
@@ -19,9 +23,24 @@ pub fn draw_ui(app: &mut SoundApp, ctx: &egui::Context) {
                 if remove_button.clicked() {
                     app.remove_all_silence_background();
                 }
-                if app.processed_ready && ui.button("Export").clicked() {
+                let export_button = ui.add_enabled(!app.is_processing, egui::Button::new("Export"));
+                if app.processed_ready && export_button.clicked() {
                     app.save_file();
                 }
+                if app.processed_ready && ui.button("Export Timeline").clicked() {
+                    app.export_timeline();
+                }
+                if app.processed_ready && ui.button("Retime Subtitles").clicked() {
+                    app.retime_subtitles();
+                }
+                let stream_button = ui.add_enabled(!app.is_processing, egui::Button::new("Process File..."));
+                if stream_button.clicked() {
+                    app.process_file_streaming();
+                }
+                let split_button = ui.add_enabled(app.file_loaded && !app.is_processing, egui::Button::new("Export Split"));
+                if split_button.clicked() {
+                    app.export_split();
+                }
             });
 
             ui.horizontal(|ui| {
@@ -29,25 +48,159 @@ pub fn draw_ui(app: &mut SoundApp, ctx: &egui::Context) {
                 ui.add(egui::Slider::new(&mut app.silence_threshold, 0.0..=0.1).text("Amplitude"));
                 ui.label("Min Silence Length (ms):");
                 ui.add(egui::Slider::new(&mut app.min_silence_len, 100..=2000).text("ms"));
+                ui.checkbox(&mut app.live_preview, "Live Preview");
+                ui.checkbox(&mut app.show_rms, "Show RMS");
+                ui.checkbox(&mut app.db_scale, "dB Scale");
+                ui.checkbox(&mut app.cache_peaks, "Cache Peaks")
+                    .on_hover_text("Save waveform peaks next to the WAV file so it opens instantly next time");
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Split File Name:");
+                ui.add(egui::TextEdit::singleline(&mut app.split_pattern).desired_width(150.0));
+                ui.label("Min Segment Length (ms):");
+                ui.add(egui::Slider::new(&mut app.split_min_len, 0..=10000).text("ms"));
+                ui.label("Export Sample Rate:");
+                let selected = app.export_sample_rate.map_or(String::from("Original"), |rate| format!("{} Hz", rate));
+                egui::ComboBox::from_id_source("export_sample_rate")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut app.export_sample_rate, None, "Original");
+                        for rate in [22050, 32000, 44100, 48000, 88200, 96000] {
+                            ui.selectable_value(&mut app.export_sample_rate, Some(rate), format!("{} Hz", rate));
+                        }
+                    });
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Output:");
+                if app.output_devices.is_none() {
+                    app.refresh_output_devices();
+                }
+                let mut device = app.output_device.clone();
+                egui::ComboBox::from_id_source("output_device")
+                    .selected_text(device.clone().unwrap_or_else(|| String::from("Default")))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut device, None, "Default");
+                        for name in app.output_devices.iter().flatten() {
+                            ui.selectable_value(&mut device, Some(name.clone()), name);
+                        }
+                    });
+                if device != app.output_device {
+                    app.set_output_device(device);
+                }
+                if ui.button("Refresh").on_hover_text("Look for newly connected output devices").clicked() {
+                    app.refresh_output_devices();
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Display:");
+                egui::ComboBox::from_id_source("display_mode")
+                    .selected_text(format!("{:?}", app.display_mode))
+                    .show_ui(ui, |ui| {
+                        for mode in [DisplayMode::Waveform, DisplayMode::Spectrogram, DisplayMode::Both] {
+                            ui.selectable_value(&mut app.display_mode, mode, format!("{:?}", mode));
+                        }
+                    });
+                if ui.button("Zoom to Fit").clicked() {
+                    app.raw_view = WaveformView::default();
+                    app.processed_view = WaveformView::default();
+                }
+                let zoom_selection = ui.add_enabled(app.selection.is_some(), egui::Button::new("Zoom to Selection"));
+                if let (true, Some((source, start, end))) = (zoom_selection.clicked(), app.selection) {
+                    let waveform = match source {
+                        PlaybackSource::Raw => &app.raw_waveform,
+                        PlaybackSource::Processed => &app.processed_waveform,
+                    };
+                    let total_samples = waveform.samples_raw.len() as f64;
+                    let channels = app.spec.map_or(1, |spec| spec.channels) as f64;
+                    let width = ui.available_width();
+                    app.view_mut(source).zoom_to_range(start, end, total_samples, channels, width);
+                    app.view_leader = source;
+                }
+                ui.checkbox(&mut app.link_views, "Link Views")
+                    .on_hover_text("Keep the original and processed waveforms showing the same audio");
+                if app.spec.is_some_and(|spec| spec.channels > 1) {
+                    ui.label("Channels:");
+                    egui::ComboBox::from_id_source("channel_view")
+                        .selected_text(format!("{:?}", app.channel_view))
+                        .show_ui(ui, |ui| {
+                            for view in [ChannelView::Lanes, ChannelView::Mixdown] {
+                                ui.selectable_value(&mut app.channel_view, view, format!("{:?}", view));
+                            }
+                        });
+                }
+                if app.display_mode != DisplayMode::Waveform {
+                    let settings = &mut app.spectrogram_settings;
+                    ui.label("FFT Size:");
+                    egui::ComboBox::from_id_source("fft_size")
+                        .selected_text(settings.fft_size.to_string())
+                        .show_ui(ui, |ui| {
+                            for size in [256, 512, 1024, 2048, 4096] {
+                                ui.selectable_value(&mut settings.fft_size, size, size.to_string());
+                            }
+                        });
+                    ui.label("Window:");
+                    egui::ComboBox::from_id_source("window_function")
+                        .selected_text(format!("{:?}", settings.window))
+                        .show_ui(ui, |ui| {
+                            for window in WindowFunction::ALL {
+                                ui.selectable_value(&mut settings.window, window, format!("{:?}", window));
+                            }
+                        });
+                    ui.label("Colors:");
+                    egui::ComboBox::from_id_source("color_map")
+                        .selected_text(format!("{:?}", settings.color_map))
+                        .show_ui(ui, |ui| {
+                            for color_map in ColorMap::ALL {
+                                ui.selectable_value(&mut settings.color_map, color_map, format!("{:?}", color_map));
+                            }
+                        });
+                    if app.spectrogram_rx.is_some() {
+                        ui.add(egui::ProgressBar::new(app.spectrogram_progress.fraction()).desired_width(120.0));
+                    }
+                }
             });
 
+            if app.is_loading {
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    ui.label("Loading...");
+                    ui.add(egui::ProgressBar::new(app.loading_progress.fraction()).show_percentage());
+                    ui.label(progress_times(&app.loading_progress));
+                    if ui.button("Cancel").clicked() {
+                        app.cancel_loading();
+                    }
+                });
+            }
+
             if app.is_processing {
                 ui.add_space(10.0);
                 ui.horizontal(|ui| {
                     ui.label("Processing...");
-                    ui.add(egui::ProgressBar::new(app.processing_progress).show_percentage());
+                    ui.add(egui::ProgressBar::new(app.processing_progress.fraction()).show_percentage());
+                    ui.label(progress_times(&app.processing_progress));
+                    if ui.button("Cancel").clicked() {
+                        app.cancel_processing();
+                    }
                 });
             }
 
+            if !app.status_message.is_empty() {
+                ui.label(&app.status_message);
+            }
+
             ui.add_space(10.0);
 
             if app.file_loaded {
                 let spec = app.spec.unwrap();
                 let sample_rate = spec.sample_rate as f32;
-                let current_raw_idx = *app.raw_waveform.current_idx.lock().unwrap() as f32;
-                let current_proc_idx = *app.processed_waveform.current_idx.lock().unwrap() as f32;
-                let current_raw_time = current_raw_idx / sample_rate;
-                let current_proc_time = current_proc_idx / sample_rate;
+                // Only the waveform loaded in the transport has a playhead
+                let show_raw_progress = app.transport.source() == Some(PlaybackSource::Raw);
+                let show_proc_progress = app.transport.source() == Some(PlaybackSource::Processed);
+                let current_idx = app.transport.position() as f64;
+                let current_time = (current_idx / spec.channels as f64) as f32 / sample_rate;
 
                 ui.label(format!(
                     "Detected {} silence segments, total {:.1}s",
@@ -58,30 +211,70 @@ pub fn draw_ui(app: &mut SoundApp, ctx: &egui::Context) {
                 ui.add_space(30.0);
 
                 ui.horizontal(|ui| {
-                    ui.label("Original:");
+                    ui.label("Transport:");
+                    let mut source = app.transport_source;
+                    egui::ComboBox::from_id_source("transport_source")
+                        .selected_text(format!("{:?}", source))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut source, TransportSource::Original, "Original");
+                            ui.add_enabled_ui(app.processed_ready, |ui| {
+                                ui.selectable_value(&mut source, TransportSource::Processed, "Processed");
+                            });
+                            ui.add_enabled_ui(app.selection.is_some(), |ui| {
+                                ui.selectable_value(&mut source, TransportSource::Selection, "Selection");
+                            });
+                        });
+                    if source != app.transport_source {
+                        app.switch_source(source);
+                    }
                     if ui.button("Play").clicked() {
-                        app.play_original();
+                        app.play();
                     }
                     if ui.button("Pause").clicked() {
-                        app.pause_original();
-                    }
-                    if ui.button("Resume").clicked() {
-                        app.resume_original();
+                        app.pause();
                     }
                     if ui.button("Stop").clicked() {
-                        app.stop_original();
+                        app.stop();
+                    }
+                    if ui
+                        .add_enabled(app.processed_ready, egui::Button::new("A/B"))
+                        .on_hover_text("Switch between original and processed at the same moment (A)")
+                        .clicked()
+                    {
+                        app.toggle_ab();
+                    }
+                    ui.label(format!("{:?} {}", app.transport.state(), format_time(current_time as f64)));
+                });
+
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    ui.label("Original:");
+                    if ui.button("Play").clicked() {
+                        app.play_source(TransportSource::Original);
                     }
                 });
 
                 ui.add_space(30.0);
 
                 ui.label("Original Waveform:");
+                let raw_overview = ui.allocate_rect(
+                    Rect::from_min_size(ui.cursor().min, egui::Vec2::new(ui.available_width(), OVERVIEW_HEIGHT)),
+                    Sense::click_and_drag(),
+                );
+                ui.add_space(4.0);
                 let raw_response = ui.allocate_rect(
                     Rect::from_min_size(ui.cursor().min, egui::Vec2::new(ui.available_width(), 200.0)),
                     Sense::click_and_drag(),
                 );
+                let raw_ruler = ui.allocate_rect(
+                    Rect::from_min_size(ui.cursor().min, egui::Vec2::new(ui.available_width(), RULER_HEIGHT)),
+                    Sense::hover(),
+                );
+                let mut rulers = vec![(raw_ruler.rect, true)];
 
                 let mut responses = vec![(raw_response.clone(), true)];
+                let mut overviews = vec![(raw_overview, true)];
 
                 if app.processed_ready {
                     ui.add_space(100.0);
@@ -89,123 +282,338 @@ pub fn draw_ui(app: &mut SoundApp, ctx: &egui::Context) {
                     ui.horizontal(|ui| {
                         ui.label("Processed:");
                         if ui.button("Play").clicked() {
-                            app.play_processed();
-                        }
-                        if ui.button("Pause").clicked() {
-                            app.pause_processed();
-                        }
-                        if ui.button("Resume").clicked() {
-                            app.resume_processed();
-                        }
-                        if ui.button("Stop").clicked() {
-                            app.stop_processed();
+                            app.play_source(TransportSource::Processed);
                         }
                     });
 
                     ui.add_space(30.0);
 
                     ui.label("Processed Waveform:");
+                    let proc_overview = ui.allocate_rect(
+                        Rect::from_min_size(ui.cursor().min, egui::Vec2::new(ui.available_width(), OVERVIEW_HEIGHT)),
+                        Sense::click_and_drag(),
+                    );
+                    ui.add_space(4.0);
+                    overviews.push((proc_overview, false));
                     let proc_response = ui.allocate_rect(
                         Rect::from_min_size(ui.cursor().min, egui::Vec2::new(ui.available_width(), 200.0)),
                         Sense::click_and_drag(),
                     );
+                    let proc_ruler = ui.allocate_rect(
+                        Rect::from_min_size(ui.cursor().min, egui::Vec2::new(ui.available_width(), RULER_HEIGHT)),
+                        Sense::hover(),
+                    );
+                    rulers.push((proc_ruler.rect, false));
                     responses.push((proc_response, false));
                 }
 
                 let painter = ui.painter();
                 let width = ui.available_width();
 
-                draw_waveform(
-                    &painter,
-                    raw_response.rect,
-                    &app.raw_waveform.samples_raw,
-                    current_raw_idx,
-                    current_raw_time,
-                    app.raw_waveform.playing_stream.is_some(),
-                    sample_rate,
-                    app.zoom,
-                    app.offset,
-                    &app.raw_waveform.silence_segments,
-                );
+                for (overview, is_original) in &overviews {
+                    let source = if *is_original { PlaybackSource::Raw } else { PlaybackSource::Processed };
+                    let waveform = if *is_original { &app.raw_waveform } else { &app.processed_waveform };
+                    let view = if *is_original { app.raw_view } else { app.processed_view };
+                    let track = Track {
+                        samples: &waveform.samples_raw,
+                        channels: spec.channels as usize,
+                        view,
+                        silence_segments: if *is_original { &waveform.silence_segments } else { &[] },
+                    };
+                    draw_overview(&painter, overview.rect, track, waveform.peaks.as_deref());
+                    // Clicking or dragging in the overview centers the view on the pointer
+                    if let Some(pos) = overview.interact_pointer_pos() {
+                        let total_samples = waveform.samples_raw.len() as f64;
+                        let sample = ((pos.x - overview.rect.min.x) / overview.rect.width()) as f64 * total_samples;
+                        app.view_mut(source).center_on(sample, total_samples, width);
+                        app.view_leader = source;
+                    }
+                }
+                app.sync_linked_views(width);
+                let (raw_view, processed_view) = (app.raw_view, app.processed_view);
+
+                for &(rect, is_original) in &rulers {
+                    let waveform = if is_original { &app.raw_waveform } else { &app.processed_waveform };
+                    let view = if is_original { raw_view } else { processed_view };
+                    draw_time_ruler(
+                        &painter,
+                        rect,
+                        waveform.samples_raw.len(),
+                        spec.channels as usize,
+                        sample_rate,
+                        view,
+                    );
+                }
+
+                // The spectrogram replaces the original waveform, or sits beneath it
+                let (waveform_rect, spectrogram_rect) = match app.display_mode {
+                    DisplayMode::Waveform => (Some(raw_response.rect), None),
+                    DisplayMode::Spectrogram => (None, Some(raw_response.rect)),
+                    DisplayMode::Both => {
+                        let rect = raw_response.rect;
+                        let (top, bottom) = rect.split_top_bottom_at_y(rect.center().y);
+                        (Some(top), Some(bottom))
+                    }
+                };
+                if let Some(rect) = spectrogram_rect {
+                    let channels = spec.channels as usize;
+                    let total_samples = app.raw_waveform.samples_raw.len() as f64;
+                    let first_frame = raw_view.sample_at(0.0, total_samples, width) as usize / channels;
+                    let last_frame = raw_view.sample_at(width, total_samples, width) as usize / channels;
+                    app.update_spectrogram_detail(first_frame..last_frame + 1, width);
+                    // Only a detail image computed with the current settings is drawn
+                    let detail = app
+                        .spectrogram_detail
+                        .as_ref()
+                        .filter(|(settings, _, _)| app.spectrogram.as_ref().is_some_and(|(s, _)| s == settings))
+                        .map(|(_, frames, texture)| (texture, frames.start * channels..frames.end * channels));
+                    let track = Track {
+                        samples: &app.raw_waveform.samples_raw,
+                        channels,
+                        view: raw_view,
+                        silence_segments: &app.raw_waveform.silence_segments,
+                    };
+                    draw_spectrogram(
+                        &painter,
+                        rect,
+                        app.spectrogram.as_ref().map(|(_, texture)| texture),
+                        detail,
+                        track,
+                        show_raw_progress.then_some(current_idx),
+                    );
+                }
+                if let Some(rect) = waveform_rect {
+                    draw_waveform(
+                        &painter,
+                        rect,
+                        &app.raw_waveform.samples_raw,
+                        spec.channels as usize,
+                        app.channel_view,
+                        app.raw_waveform.peaks.as_deref(),
+                        current_idx,
+                        current_time,
+                        show_raw_progress,
+                        raw_view,
+                        &app.raw_waveform.silence_segments,
+                        app.show_rms,
+                        app.db_scale,
+                        Some(app.silence_threshold),
+                    );
+                }
                 if app.processed_ready {
+                    // Show where the position under the cursor ended up in the processed audio
+                    if let Some(pos) = raw_response.hover_pos() {
+                        let total_samples = app.raw_waveform.samples_raw.len() as f64;
+                        let sample_idx = raw_view.sample_at(pos.x - raw_response.rect.min.x, total_samples, width) as usize;
+                        let label = if app.timeline.is_removed(sample_idx) {
+                            String::from("Removed")
+                        } else {
+                            let processed_idx = app.timeline.original_to_processed(sample_idx);
+                            format!("Processed {:.3}s", processed_idx as f32 / spec.channels as f32 / sample_rate)
+                        };
+                        painter.text(
+                            Pos2::new(pos.x + 8.0, raw_response.rect.min.y + 10.0),
+                            Align2::LEFT_TOP,
+                            label,
+                            FontId::default(),
+                            Color32::DARK_BLUE,
+                        );
+                    }
+
                     if let Some(proc_response) = responses.last().map(|(r, _)| r) {
                         draw_waveform(
                             &painter,
                             proc_response.rect,
                             &app.processed_waveform.samples_raw,
-                            current_proc_idx,
-                            current_proc_time,
-                            app.processed_waveform.playing_stream.is_some(),
-                            sample_rate,
-                            app.zoom,
-                            app.offset,
+                            spec.channels as usize,
+                            app.channel_view,
+                            app.processed_waveform.peaks.as_deref(),
+                            current_idx,
+                            current_time,
+                            show_proc_progress,
+                            processed_view,
                             &[], // Processed waveform does not display silence markers, as they have been removed
+                            app.show_rms,
+                            app.db_scale,
+                            None,
                         );
+
+                        // Show where the position under the cursor came from in the original audio
+                        if let Some(pos) = proc_response.hover_pos() {
+                            let total_samples = app.processed_waveform.samples_raw.len() as f64;
+                            let sample_idx = processed_view.sample_at(pos.x - proc_response.rect.min.x, total_samples, width) as usize;
+                            let original_idx = app.timeline.processed_to_original(sample_idx);
+                            let original_time = original_idx as f32 / spec.channels as f32 / sample_rate;
+                            painter.text(
+                                Pos2::new(pos.x + 8.0, proc_response.rect.min.y + 10.0),
+                                Align2::LEFT_TOP,
+                                format!("Original {:.3}s", original_time),
+                                FontId::default(),
+                                Color32::DARK_BLUE,
+                            );
+                        }
+                    }
+                }
+
+                if let Some((source, start, end)) = app.selection {
+                    let is_original = source == PlaybackSource::Raw;
+                    let waveform = if is_original { &app.raw_waveform } else { &app.processed_waveform };
+                    let view = if is_original { raw_view } else { processed_view };
+                    if let Some((response, _)) = responses.iter().find(|(_, o)| *o == is_original) {
+                        draw_selection(&painter, response.rect, start, end, waveform.samples_raw.len(), view);
                     }
                 }
 
+                let keyboard = !ctx.wants_keyboard_input();
                 ui.input(|i| {
-                    handle_waveform_interaction(app, i, &responses, width);
+                    handle_waveform_interaction(app, i, &responses, width, keyboard);
                 });
-
-                ctx.request_repaint();
             } else {
                 ui.label("Please load a WAV file first");
             }
         });
     });
+
+    // Background jobs don't generate input events, so keep polling them while they run
+    if app.has_pending_work() {
+        ctx.request_repaint();
+    } else {
+        ctx.request_repaint_after(IDLE_REPAINT);
+    }
 }
 
-fn handle_waveform_interaction(app: &mut SoundApp, input: &egui::InputState, responses: &[(Response, bool)], width: f32) {
-    for &(ref response, is_original) in responses {
-        let rect = response.rect;
+/// How often to redraw while nothing is running, to pick up events that don't wake the UI,
+/// like an error reported by the audio stream.
+const IDLE_REPAINT: std::time::Duration = std::time::Duration::from_millis(500);
 
-        // Zoom
-        if input.scroll_delta.y != 0.0 && rect.contains(input.pointer.hover_pos().unwrap_or_default()) {
-            let zoom_factor = if input.scroll_delta.y > 0.0 { 1.1 } else { 0.9 };
-            app.zoom *= zoom_factor;
-            app.zoom = app.zoom.max(0.1).min(100.0);
+/// Elapsed time and estimated time remaining, e.g. "0:12 elapsed, 0:30 left".
+fn progress_times(progress: &Progress) -> String {
+    let format = |d: std::time::Duration| format!("{}:{:02}", d.as_secs() / 60, d.as_secs() % 60);
+    match progress.eta() {
+        Some(eta) => format!("{} elapsed, {} left", format(progress.elapsed()), format(eta)),
+        None => format!("{} elapsed", format(progress.elapsed())),
+    }
+}
+
+fn handle_waveform_interaction(
+    app: &mut SoundApp,
+    input: &egui::InputState,
+    responses: &[(Response, bool)],
+    width: f32,
+    keyboard: bool,
+) {
+    let channels = app.spec.map_or(1, |spec| spec.channels) as f64;
+    let hover_pos = input.pointer.hover_pos();
+    let total_samples_of = |app: &SoundApp, source: PlaybackSource| {
+        let waveform = match source {
+            PlaybackSource::Raw => &app.raw_waveform,
+            PlaybackSource::Processed => &app.processed_waveform,
+        };
+        waveform.samples_raw.len() as f64
+    };
+    let source_of = |is_original: bool| if is_original { PlaybackSource::Raw } else { PlaybackSource::Processed };
+
+    // Keyboard zoom applies to the view under the pointer, anchored at the pointer, or to the
+    // original view, anchored at its center
+    if keyboard {
+        let factor = if input.key_pressed(egui::Key::PlusEquals) {
+            1.25
+        } else if input.key_pressed(egui::Key::Minus) {
+            0.8
+        } else {
+            1.0
+        };
+        if factor != 1.0 {
+            let hovered = responses.iter().find(|(r, _)| hover_pos.is_some_and(|p| r.rect.contains(p)));
+            let anchor_x = match (hovered, hover_pos) {
+                (Some((response, _)), Some(pos)) => pos.x - response.rect.min.x,
+                _ => width / 2.0,
+            };
+            let source = source_of(hovered.map_or(true, |&(_, is_original)| is_original));
+            let total_samples = total_samples_of(app, source);
+            app.view_mut(source).zoom_at(factor, anchor_x, total_samples, channels, width);
+            app.view_leader = source;
         }
+        if input.key_pressed(egui::Key::Num0) {
+            app.raw_view = WaveformView::default();
+            app.processed_view = WaveformView::default();
+        }
+        if input.key_pressed(egui::Key::A) {
+            app.toggle_ab();
+        }
+    }
 
-        // Drag
-        if input.pointer.primary_down() && rect.contains(input.pointer.hover_pos().unwrap_or_default()) {
-            let delta = input.pointer.delta();
-            let total_samples = if is_original {
-                app.raw_waveform.samples_raw.len()
-            } else {
-                app.processed_waveform.samples_raw.len()
-            } as f32;
-            let samples_per_pixel = total_samples / width / app.zoom;
-            app.offset -= delta.x;
-            app.offset = app.offset.max(0.0).min(total_samples / samples_per_pixel - width);
+    for &(ref response, is_original) in responses {
+        let rect = response.rect;
+        let Some(pos) = hover_pos.filter(|&pos| rect.contains(pos)) else {
+            continue;
+        };
+        let source = source_of(is_original);
+        let total_samples = total_samples_of(app, source);
+
+        // Zoom: pinch and ctrl+scroll arrive as a zoom factor, the mouse wheel as a scroll
+        let zoom_delta = input.zoom_delta();
+        let factor = if zoom_delta != 1.0 {
+            zoom_delta as f64
+        } else if input.scroll_delta.y > 0.0 {
+            1.1
+        } else if input.scroll_delta.y < 0.0 {
+            0.9
+        } else {
+            1.0
+        };
+        if factor != 1.0 {
+            app.view_mut(source).zoom_at(factor, pos.x - rect.min.x, total_samples, channels, width);
+            app.view_leader = source;
         }
 
-        if input.pointer.primary_clicked() && rect.contains(input.pointer.hover_pos().unwrap_or_default()) {
-            if let Some(pos) = input.pointer.hover_pos() {
-                let total_samples = if is_original {
-                    app.raw_waveform.samples_raw.len()
-                } else {
-                    app.processed_waveform.samples_raw.len()
-                } as f32;
-                let samples_per_pixel = total_samples / width / app.zoom;
-                let sample_idx = ((pos.x - rect.min.x + app.offset) * samples_per_pixel) as usize;
-                app.jump_to_position(sample_idx, is_original);
+        let view = *app.view_mut(source);
+        let sample_at = |x: f32| (view.sample_at(x - rect.min.x, total_samples, width).max(0.0) as usize).min(total_samples as usize);
+
+        if input.pointer.primary_down() {
+            if input.modifiers.shift {
+                // Shift-drag selects
+                if let Some(origin) = input.pointer.press_origin().filter(|&origin| rect.contains(origin)) {
+                    let (a, b) = (sample_at(origin.x), sample_at(pos.x));
+                    app.selection = (a != b).then_some((source, a.min(b), a.max(b)));
+                }
+            } else if input.pointer.delta().x != 0.0 {
+                // Drag pans
+                app.view_mut(source).pan(-input.pointer.delta().x, width);
+                app.view_leader = source;
             }
         }
+
+        if input.pointer.primary_clicked() && !input.modifiers.shift {
+            app.jump_to_position(sample_at(pos.x), is_original);
+        }
     }
 }
 
+/// A waveform's audio as its view shows it, which every part drawn for that waveform needs.
+#[derive(Clone, Copy)]
+struct Track<'a> {
+    samples: &'a [i16],
+    channels: usize,
+    view: WaveformView,
+    silence_segments: &'a [(usize, usize)],
+}
+
 fn draw_waveform(
     painter: &Painter,
     rect: Rect,
-    samples_raw: &Arc<Vec<i16>>,
-    current_idx: f32,
+    samples_raw: &[i16],
+    channels: usize,
+    channel_view: ChannelView,
+    peaks: Option<&PeakPyramid>,
+    current_idx: f64,
     current_time: f32,
     show_progress: bool,
-    sample_rate: f32,
-    zoom: f32,
-    offset: f32,
+    view: WaveformView,
     silence_segments: &[(usize, usize)],
+    show_rms: bool,
+    db_scale: bool,
+    threshold: Option<f32>,
 ) {
     let pos = rect.min;
     let height = rect.height();
@@ -213,14 +621,16 @@ fn draw_waveform(
 
     painter.rect_filled(rect, 0.0, Color32::WHITE);
 
-    let total_samples = samples_raw.len() as f32;
-    let total_seconds = total_samples / sample_rate;
-    let samples_per_pixel = total_samples / width / zoom;
-    let start_sample = (offset * samples_per_pixel).max(0.0).min(total_samples - 1.0) as usize;
+    let total_samples = samples_raw.len() as f64;
+    let total_frames = samples_raw.len() / channels;
+    // Lanes are drawn per frame; a frame covers `channels` interleaved samples
+    let frames_per_pixel = view.samples_per_pixel(total_samples, width) / channels as f64;
+    let start_frame = (view.sample_at(0.0, total_samples, width) / channels as f64).max(0.0);
+    let frame_at = |x: usize| (start_frame + x as f64 * frames_per_pixel) as usize;
 
     for &(start, end) in silence_segments {
-        let start_x = pos.x + ((start as f32 - offset * samples_per_pixel) / samples_per_pixel).max(0.0);
-        let end_x = pos.x + ((end as f32 - offset * samples_per_pixel) / samples_per_pixel).min(width);
+        let start_x = pos.x + view.x_of(start as f64, total_samples, width).max(0.0);
+        let end_x = pos.x + view.x_of(end as f64, total_samples, width).min(width);
         if start_x < end_x && start_x < pos.x + width && end_x > pos.x {
             painter.rect_filled(
                 Rect::from_min_max(Pos2::new(start_x, pos.y), Pos2::new(end_x, pos.y + height)),
@@ -230,19 +640,118 @@ fn draw_waveform(
         }
     }
 
-    let mut points = Vec::new();
-    for x in 0..width as usize {
-        let sample_idx = (start_sample as f32 + x as f32 * samples_per_pixel) as usize;
-        if sample_idx < samples_raw.len() {
-            let y = samples_raw[sample_idx] as f32 / i16::MAX as f32;
-            let y_pos = pos.y + height * (0.5 - y * 0.5);
-            points.push(Pos2::new(pos.x + x as f32, y_pos));
+    // `None` is the mixdown of all channels
+    let lanes: Vec<Option<usize>> = if channels == 1 {
+        vec![Some(0)]
+    } else {
+        match channel_view {
+            ChannelView::Lanes => (0..channels).map(Some).collect(),
+            ChannelView::Mixdown => vec![None],
+        }
+    };
+    let lane_height = height / lanes.len() as f32;
+    let frame_value = |frame: usize, lane: Option<usize>| {
+        let frame = &samples_raw[frame * channels..(frame + 1) * channels];
+        match lane {
+            Some(channel) => frame[channel],
+            None => (frame.iter().map(|&s| s as i32).sum::<i32>() / channels as i32) as i16,
+        }
+    };
+
+    for (lane_idx, &lane) in lanes.iter().enumerate() {
+        let lane_top = pos.y + lane_idx as f32 * lane_height;
+        let to_y = |sample: f32| lane_top + lane_height * (0.5 - scale_amplitude(sample / i16::MAX as f32, db_scale) * 0.5);
+        if lane_idx > 0 {
+            painter.line_segment(
+                [Pos2::new(pos.x, lane_top), Pos2::new(pos.x + width, lane_top)],
+                Stroke::new(1.0, Color32::from_gray(160)),
+            );
+        }
+        if lanes.len() > 1 {
+            painter.text(
+                Pos2::new(pos.x + 4.0, lane_top + 2.0),
+                Align2::LEFT_TOP,
+                channel_name(lane_idx, channels),
+                FontId::default(),
+                Color32::GRAY,
+            );
+        }
+
+        // A frame is silent when the mean of its channels' absolute amplitudes is below the
+        // threshold; for mono, or channels that move together, that is within these lines
+        if let Some(threshold) = threshold {
+            let level = threshold * i16::MAX as f32;
+            for y in [to_y(level), to_y(-level)] {
+                painter.line_segment(
+                    [Pos2::new(pos.x, y), Pos2::new(pos.x + width, y)],
+                    Stroke::new(1.0, Color32::from_rgb(230, 140, 0)),
+                );
+            }
+            if lane_idx == 0 {
+                painter.text(
+                    Pos2::new(pos.x + width - 4.0, to_y(level) - 2.0),
+                    Align2::RIGHT_BOTTOM,
+                    format_db(threshold),
+                    FontId::default(),
+                    Color32::from_rgb(230, 140, 0),
+                );
+            }
+        }
+
+        if frames_per_pixel <= 1.0 {
+            // Zoomed in past one frame per pixel: connect the individual samples
+            let mut points = Vec::new();
+            for x in 0..width as usize {
+                let frame = frame_at(x);
+                if frame < total_frames {
+                    points.push(Pos2::new(pos.x + x as f32, to_y(frame_value(frame, lane) as f32)));
+                }
+            }
+            painter.add(egui::Shape::line(points, Stroke::new(1.0, Color32::BLACK)));
+            continue;
+        }
+
+        // Each pixel column spans every frame it covers, so no peak or gap is skipped
+        for x in 0..width as usize {
+            let from = frame_at(x);
+            let to = frame_at(x + 1).min(total_frames);
+            if from >= to {
+                break;
+            }
+            // Wide columns read the peak pyramid, narrow ones scan the few frames they cover
+            let pyramid_lane = peaks.map(|peaks| (peaks, lane.unwrap_or_else(|| peaks.mixdown_lane())));
+            let peak = pyramid_lane
+                .and_then(|(peaks, l)| peaks.range(l, from, to, |frame| frame_value(frame, lane)))
+                .unwrap_or_else(|| {
+                let (mut min, mut max, mut sum_squares) = (i16::MAX, i16::MIN, 0.0f64);
+                for frame in from..to {
+                    let s = frame_value(frame, lane);
+                    min = min.min(s);
+                    max = max.max(s);
+                    sum_squares += (s as f64) * (s as f64);
+                }
+                Peak { min, max, mean_square: (sum_squares / (to - from) as f64) as f32 }
+            });
+            let (min, max) = (peak.min, peak.max);
+            let column_x = pos.x + x as f32 + 0.5;
+            let (top, bottom) = (to_y(max as f32), to_y(min as f32).max(to_y(max as f32) + 1.0));
+            painter.line_segment(
+                [Pos2::new(column_x, top), Pos2::new(column_x, bottom)],
+                Stroke::new(1.0, Color32::BLACK),
+            );
+
+            if show_rms {
+                let rms = peak.rms();
+                painter.line_segment(
+                    [Pos2::new(column_x, to_y(rms)), Pos2::new(column_x, to_y(-rms))],
+                    Stroke::new(1.0, Color32::from_rgb(90, 130, 210)),
+                );
+            }
         }
     }
-    painter.add(egui::Shape::line(points, Stroke::new(1.0, Color32::BLACK)));
 
     if show_progress && current_idx < total_samples {
-        let progress_x = pos.x + (current_idx / total_samples * width * zoom) - offset;
+        let progress_x = pos.x + view.x_of(current_idx, total_samples, width);
         if progress_x >= pos.x && progress_x <= pos.x + width {
             painter.line_segment(
                 [Pos2::new(progress_x, pos.y), Pos2::new(progress_x, pos.y + height)],
@@ -257,17 +766,246 @@ fn draw_waveform(
             Color32::RED,
         );
     }
+}
 
-    let time_step = (total_seconds / width * 100.0).max(1.0);
-    for sec in (0..total_seconds as usize).step_by(time_step as usize) {
-        let x = pos.x + (sec as f32 / total_seconds * width * zoom) - offset;
-        if x >= pos.x && x <= pos.x + width {
+/// Shades the selected samples `start..end` of a waveform view.
+fn draw_selection(painter: &Painter, rect: Rect, start: usize, end: usize, total_samples: usize, view: WaveformView) {
+    let (total_samples, width) = (total_samples as f64, rect.width());
+    let start_x = (rect.min.x + view.x_of(start as f64, total_samples, width)).max(rect.min.x);
+    let end_x = (rect.min.x + view.x_of(end as f64, total_samples, width)).min(rect.max.x);
+    if start_x < end_x {
+        painter.rect_filled(
+            Rect::from_min_max(Pos2::new(start_x, rect.min.y), Pos2::new(end_x, rect.max.y)),
+            0.0,
+            Color32::from_rgba_unmultiplied(70, 110, 200, 60),
+        );
+    }
+}
+
+/// Height of the time ruler below each waveform.
+const RULER_HEIGHT: f32 = 24.0;
+
+/// Tick spacings in seconds the ruler picks from, from milliseconds to an hour, each with the
+/// number of minor ticks it is divided into.
+const RULER_STEPS: [(f32, u32); 22] = [
+    (0.001, 5), (0.002, 4), (0.005, 5), (0.01, 5), (0.02, 4), (0.05, 5), (0.1, 5), (0.2, 4), (0.5, 5),
+    (1.0, 5), (2.0, 4), (5.0, 5), (10.0, 5), (15.0, 3), (30.0, 6), (60.0, 6), (120.0, 4), (300.0, 5),
+    (600.0, 5), (900.0, 3), (1800.0, 6), (3600.0, 6),
+];
+
+/// Minimum distance between labelled ticks.
+const RULER_LABEL_SPACING: f32 = 100.0;
+
+/// Draws a time ruler for the visible part of a waveform. Pixel `x` shows sample
+/// `(x + offset) * samples_per_pixel`, the same mapping used for dragging, zooming and seeking.
+fn draw_time_ruler(
+    painter: &Painter,
+    rect: Rect,
+    total_samples: usize,
+    channels: usize,
+    sample_rate: f32,
+    view: WaveformView,
+) {
+    let width = rect.width();
+    painter.rect_filled(rect, 0.0, Color32::from_gray(240));
+    if total_samples == 0 {
+        return;
+    }
+
+    let total_samples = total_samples as f64;
+    let seconds_per_sample = 1.0 / channels as f64 / sample_rate as f64;
+    let seconds_per_pixel = view.samples_per_pixel(total_samples, width) * seconds_per_sample;
+    let (step, minor_count) = RULER_STEPS
+        .iter()
+        .copied()
+        .find(|(step, _)| (*step as f64 / seconds_per_pixel) as f32 >= RULER_LABEL_SPACING)
+        .unwrap_or(RULER_STEPS[RULER_STEPS.len() - 1]);
+    let minor_step = step as f64 / minor_count as f64;
+
+    let start_seconds = view.sample_at(0.0, total_samples, width).max(0.0) * seconds_per_sample;
+    let end_seconds = view.sample_at(width, total_samples, width) * seconds_per_sample;
+    let to_x = |seconds: f64| rect.min.x + view.x_of(seconds / seconds_per_sample, total_samples, width);
+
+    // Ticks are counted in whole minor steps so they don't drift when zoomed far in
+    let first = (start_seconds / minor_step).floor() as i64;
+    let last = (end_seconds / minor_step).ceil() as i64;
+    for i in first..=last {
+        let seconds = i as f64 * minor_step;
+        let x = to_x(seconds);
+        if x < rect.min.x || x > rect.max.x {
+            continue;
+        }
+        let major = i % minor_count as i64 == 0;
+        let tick_height = if major { rect.height() * 0.5 } else { rect.height() * 0.25 };
+        painter.line_segment(
+            [Pos2::new(x, rect.min.y), Pos2::new(x, rect.min.y + tick_height)],
+            Stroke::new(1.0, Color32::DARK_GRAY),
+        );
+        if major {
             painter.text(
-                Pos2::new(x, pos.y + height + 20.0),
-                Align2::CENTER_TOP,
-                format!("{}s", sec),
-                FontId::default(),
-                Color32::WHITE,
+                Pos2::new(x + 2.0, rect.min.y + tick_height),
+                Align2::LEFT_TOP,
+                format_time(seconds),
+                FontId::monospace(10.0),
+                Color32::BLACK,
+            );
+        }
+    }
+}
+
+/// Formats seconds as `hh:mm:ss.mmm`.
+fn format_time(seconds: f64) -> String {
+    let ms = (seconds * 1000.0).round().max(0.0) as u64;
+    format!("{:02}:{:02}:{:02}.{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000)
+}
+
+/// Height of the strip above each waveform that shows the whole file.
+const OVERVIEW_HEIGHT: f32 = 40.0;
+
+/// Draws the whole file as a thin strip with its silence segments, and a rectangle around the
+/// part currently visible in the waveform view.
+fn draw_overview(painter: &Painter, rect: Rect, track: Track, peaks: Option<&PeakPyramid>) {
+    let Track { samples: samples_raw, channels, view, silence_segments } = track;
+    let pos = rect.min;
+    let width = rect.width();
+    painter.rect_filled(rect, 0.0, Color32::from_gray(235));
+
+    let total_samples = samples_raw.len() as f32;
+    for &(start, end) in silence_segments {
+        let start_x = pos.x + start as f32 / total_samples * width;
+        let end_x = pos.x + end as f32 / total_samples * width;
+        painter.rect_filled(
+            Rect::from_min_max(Pos2::new(start_x, rect.min.y), Pos2::new(end_x, rect.max.y)),
+            0.0,
+            Color32::from_gray(200),
+        );
+    }
+
+    // Scanning the whole file every frame would be too slow, so the overview waits for the peaks
+    if let Some(peaks) = peaks {
+        let total_frames = samples_raw.len() / channels;
+        let frames_per_pixel = total_frames as f32 / width;
+        let to_y = |sample: i16| rect.center().y - sample as f32 / i16::MAX as f32 * rect.height() / 2.0;
+        for x in 0..width as usize {
+            let from = (x as f32 * frames_per_pixel) as usize;
+            let to = ((x + 1) as f32 * frames_per_pixel) as usize;
+            let mixdown = |frame: usize| {
+                let frame = &samples_raw[frame * channels..(frame + 1) * channels];
+                (frame.iter().map(|&s| s as i32).sum::<i32>() / channels as i32) as i16
+            };
+            if let Some(peak) = peaks.range(peaks.mixdown_lane(), from, to, mixdown) {
+                let column_x = pos.x + x as f32 + 0.5;
+                painter.line_segment(
+                    [Pos2::new(column_x, to_y(peak.max)), Pos2::new(column_x, to_y(peak.min))],
+                    Stroke::new(1.0, Color32::DARK_GRAY),
+                );
+            }
+        }
+    }
+
+    // The view shows `width` of the `width * zoom` pixels the whole file spans
+    let view = Rect::from_min_max(
+        Pos2::new(pos.x + (view.offset / view.zoom) as f32, rect.min.y),
+        Pos2::new(pos.x + ((view.offset + width as f64) / view.zoom) as f32, rect.max.y),
+    );
+    painter.rect_filled(view, 0.0, Color32::from_rgba_unmultiplied(70, 110, 200, 40));
+    painter.rect_stroke(view, 0.0, Stroke::new(1.0, Color32::from_rgb(70, 110, 200)));
+}
+
+/// Lowest level shown on the dB scale; quieter samples sit on the center line.
+const DB_FLOOR: f32 = -60.0;
+
+/// Maps a sample in -1.0..=1.0 to a signed height in -1.0..=1.0, linearly or on a dB scale
+/// where `DB_FLOOR` is the center line and 0 dB the edge of the lane.
+fn scale_amplitude(sample: f32, db_scale: bool) -> f32 {
+    if !db_scale {
+        return sample;
+    }
+    let db = 20.0 * sample.abs().max(1e-6).log10();
+    sample.signum() * ((db - DB_FLOOR) / -DB_FLOOR).clamp(0.0, 1.0)
+}
+
+/// A linear amplitude in dB, with 0 shown as "-inf dB".
+fn format_db(amplitude: f32) -> String {
+    if amplitude > 0.0 {
+        format!("{:.1} dB", 20.0 * amplitude.log10())
+    } else {
+        "-inf dB".to_string()
+    }
+}
+
+/// "L"/"R" for stereo files, "Ch 1", "Ch 2", ... otherwise.
+fn channel_name(channel: usize, channels: usize) -> String {
+    match (channels, channel) {
+        (2, 0) => String::from("L"),
+        (2, _) => String::from("R"),
+        _ => format!("Ch {}", channel + 1),
+    }
+}
+
+/// Draws the spectrogram texture for the visible part of the file, with silence segments
+/// shaded on top so they line up with the waveform view. `detail` is a sharper image of the
+/// given sample range, drawn over the whole-file image where it is available. `playhead` is the
+/// playback position, if it is shown.
+fn draw_spectrogram(
+    painter: &Painter,
+    rect: Rect,
+    texture: Option<&egui::TextureHandle>,
+    detail: Option<(&egui::TextureHandle, std::ops::Range<usize>)>,
+    track: Track,
+    playhead: Option<f64>,
+) {
+    let view = track.view;
+    let pos = rect.min;
+    let width = rect.width();
+    painter.rect_filled(rect, 0.0, Color32::BLACK);
+
+    let Some(texture) = texture else {
+        painter.text(rect.center(), Align2::CENTER_CENTER, "Computing spectrogram...", FontId::default(), Color32::WHITE);
+        return;
+    };
+
+    let total = track.samples.len() as f64;
+    let start_sample = view.sample_at(0.0, total, width).max(0.0);
+    let end_sample = view.sample_at(width, total, width).min(total);
+    // Draws the visible part of an image that covers samples `from..to`
+    let draw_image = |texture: &egui::TextureHandle, from: f64, to: f64| {
+        let (start, end) = (start_sample.max(from), end_sample.min(to));
+        if start < end {
+            let image_rect = Rect::from_min_max(
+                Pos2::new(pos.x + view.x_of(start, total, width), rect.min.y),
+                Pos2::new(pos.x + view.x_of(end, total, width), rect.max.y),
+            );
+            let uv = Rect::from_min_max(
+                Pos2::new(((start - from) / (to - from)) as f32, 0.0),
+                Pos2::new(((end - from) / (to - from)) as f32, 1.0),
+            );
+            painter.image(texture.id(), image_rect, uv, Color32::WHITE);
+        }
+    };
+    draw_image(texture, 0.0, total);
+    if let Some((detail, range)) = detail {
+        draw_image(detail, range.start as f64, range.end as f64);
+    }
+
+    for &(start, end) in track.silence_segments {
+        let start_x = pos.x + view.x_of(start as f64, total, width).max(0.0);
+        let end_x = pos.x + view.x_of(end as f64, total, width).min(width);
+        if start_x < end_x && start_x < pos.x + width && end_x > pos.x {
+            painter.rect_filled(
+                Rect::from_min_max(Pos2::new(start_x, rect.min.y), Pos2::new(end_x, rect.max.y)),
+                0.0,
+                Color32::from_white_alpha(90),
+            );
+        }
+    }
+
+    if let Some(current_idx) = playhead.filter(|&idx| idx < total) {
+        let progress_x = pos.x + view.x_of(current_idx, total, width);
+        if progress_x >= pos.x && progress_x <= pos.x + width {
+            painter.line_segment(
+                [Pos2::new(progress_x, rect.min.y), Pos2::new(progress_x, rect.max.y)],
+                Stroke::new(1.0, Color32::RED),
             );
         }
     }
@@ -276,6 +1014,7 @@ fn draw_waveform(
 impl eframe::App for SoundApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.update_processing();
+        self.update_spectrogram(ctx);
         draw_ui(self, ctx);
     }
 }
\ No newline at end of file
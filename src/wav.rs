@@ -0,0 +1,157 @@
+use crate::job::CancelToken;
+use hound::{SampleFormat, WavReader, WavSpec};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+/// Number of samples decoded between progress reports and cancellation checks.
+const PROGRESS_INTERVAL: usize = 1 << 16;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// A 16-bit PCM WAV file mapped into memory. The data chunk is exposed directly as a sample
+/// slice, so opening is instant and the OS only pages in the parts that are actually read.
+pub struct MappedWav {
+    mmap: Mmap,
+    data_offset: usize,
+    len: usize,
+    spec: WavSpec,
+}
+
+impl MappedWav {
+    /// Maps `path` if it is a little-endian 16-bit PCM WAV file. Other formats return an
+    /// `InvalidData` error so the caller can fall back to decoding with hound.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        if cfg!(target_endian = "big") {
+            return Err(invalid("Memory mapping requires a little-endian host"));
+        }
+
+        let file = File::open(path)?;
+        // Safety: the file is opened read-only. As with any memory map, modifying it from
+        // another process while it is open is not supported.
+        let mmap = unsafe { Mmap::map(&file)? };
+        if mmap.len() < 12 || &mmap[0..4] != b"RIFF" || &mmap[8..12] != b"WAVE" {
+            return Err(invalid("Not a RIFF WAVE file"));
+        }
+
+        let read_u16 = |at: usize| u16::from_le_bytes([mmap[at], mmap[at + 1]]);
+        let read_u32 = |at: usize| u32::from_le_bytes([mmap[at], mmap[at + 1], mmap[at + 2], mmap[at + 3]]);
+
+        let mut spec = None;
+        let mut pos = 12;
+        while pos + 8 <= mmap.len() {
+            let id = &mmap[pos..pos + 4];
+            let size = read_u32(pos + 4) as usize;
+            let body = pos + 8;
+            match id {
+                b"fmt " => {
+                    if size < 16 || body + 16 > mmap.len() {
+                        return Err(invalid("Truncated fmt chunk"));
+                    }
+                    let mut format = read_u16(body);
+                    if format == WAVE_FORMAT_EXTENSIBLE && size >= 26 && body + 26 <= mmap.len() {
+                        // The sub-format GUID starts with the actual format tag
+                        format = read_u16(body + 24);
+                    }
+                    let bits_per_sample = read_u16(body + 14);
+                    if format != WAVE_FORMAT_PCM || bits_per_sample != 16 {
+                        return Err(invalid("Only 16-bit PCM can be memory mapped"));
+                    }
+                    let channels = read_u16(body + 2);
+                    let sample_rate = read_u32(body + 4);
+                    // Later code divides by these, so reject files that would make it panic
+                    if channels == 0 || sample_rate == 0 || read_u16(body + 12) as usize != channels as usize * 2 {
+                        return Err(invalid("Invalid channel count, sample rate or block alignment"));
+                    }
+                    spec = Some(WavSpec {
+                        channels,
+                        sample_rate,
+                        bits_per_sample,
+                        sample_format: SampleFormat::Int,
+                    });
+                }
+                b"data" => {
+                    let spec = spec.ok_or_else(|| invalid("data chunk before fmt chunk"))?;
+                    if body % 2 != 0 {
+                        return Err(invalid("Misaligned data chunk"));
+                    }
+                    // Streamed or truncated files may report a size past the end of the file
+                    let bytes = size.min(mmap.len() - body);
+                    return Ok(Self {
+                        data_offset: body,
+                        len: bytes / 2,
+                        spec,
+                        mmap,
+                    });
+                }
+                _ => {}
+            }
+            // Chunks are padded to an even number of bytes
+            pos = body + size + (size & 1);
+        }
+        Err(invalid("No data chunk"))
+    }
+
+    pub fn spec(&self) -> WavSpec {
+        self.spec
+    }
+
+    pub fn samples(&self) -> &[i16] {
+        let data = &self.mmap[self.data_offset..self.data_offset + self.len * 2];
+        // Safety: chunks start at even offsets and the map is page aligned, so the data is
+        // aligned for i16, and the host is little-endian like the file.
+        unsafe { std::slice::from_raw_parts(data.as_ptr() as *const i16, self.len) }
+    }
+}
+
+/// Sample storage for a waveform: either decoded into memory or mapped from the file on disk.
+pub enum SampleBuffer {
+    Owned(Vec<i16>),
+    Mapped(MappedWav),
+}
+
+impl Deref for SampleBuffer {
+    type Target = [i16];
+
+    fn deref(&self) -> &[i16] {
+        match self {
+            SampleBuffer::Owned(samples) => samples,
+            SampleBuffer::Mapped(mapped) => mapped.samples(),
+        }
+    }
+}
+
+/// Reads a WAV file for editing. 16-bit PCM files are memory mapped, anything else is decoded
+/// with hound. Returns `Ok(None)` if the token is cancelled before decoding finishes.
+pub fn read_wav<F: FnMut(f32)>(
+    path: &Path,
+    cancel: &CancelToken,
+    mut on_progress: F,
+) -> Result<Option<(WavSpec, SampleBuffer)>, hound::Error> {
+    match MappedWav::open(path) {
+        Ok(mapped) => {
+            on_progress(1.0);
+            return Ok(Some((mapped.spec(), SampleBuffer::Mapped(mapped))));
+        }
+        Err(err) => println!("Not memory mapping {:?}: {}", path, err),
+    }
+
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+    let total_samples = reader.len() as usize;
+    let mut samples = Vec::with_capacity(total_samples);
+    for sample in reader.samples::<i16>() {
+        samples.push(sample?);
+        if samples.len() % PROGRESS_INTERVAL == 0 {
+            if cancel.is_cancelled() {
+                return Ok(None);
+            }
+            on_progress(samples.len() as f32 / total_samples as f32);
+        }
+    }
+    Ok(Some((spec, SampleBuffer::Owned(samples))))
+}
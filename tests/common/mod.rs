@@ -0,0 +1,53 @@
+//! Fixtures shared by the integration tests.
+
+// Each test crate compiles this module and uses only part of it
+#![allow(dead_code)]
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::path::{Path, PathBuf};
+
+/// 16-bit PCM at 1 kHz, so a millisecond is one frame and durations read as frame counts.
+pub fn spec(channels: u16) -> WavSpec {
+    WavSpec {
+        channels,
+        sample_rate: 1000,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    }
+}
+
+/// A path in the temp directory, unique to this test process, deleted when dropped so a failing
+/// assertion doesn't leave the file behind.
+pub struct TempFile(PathBuf);
+
+impl TempFile {
+    pub fn new(name: &str) -> Self {
+        Self(std::env::temp_dir().join(format!("solid_meme_{}_{}.wav", name, std::process::id())))
+    }
+
+    /// Deletes `path` when dropped, for files the code under test creates next to another.
+    pub fn guard(path: PathBuf) -> Self {
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Writes `samples` as a WAV file with the layout of `spec`.
+pub fn write_wav(name: &str, spec: WavSpec, samples: &[i16]) -> TempFile {
+    let file = TempFile::new(name);
+    let mut writer = WavWriter::create(file.path(), spec).unwrap();
+    for &sample in samples {
+        writer.write_sample(sample).unwrap();
+    }
+    writer.finalize().unwrap();
+    file
+}
@@ -0,0 +1,84 @@
+//! Parallel silence detection stitches runs across chunk boundaries, so it must find exactly
+//! what a single sequential pass finds.
+
+mod common;
+
+use common::spec;
+use hound::WavSpec;
+use solid_meme::audio::{detect_silence_chunked, detect_silence_parallel, SilenceDetector};
+use solid_meme::job::CancelToken;
+
+const LOUD: i16 = 5000;
+const THRESHOLD: f32 = 0.01;
+
+/// Builds interleaved audio from runs of loud (`true`) or silent frames.
+fn audio(channels: usize, runs: &[(bool, usize)]) -> Vec<i16> {
+    runs.iter()
+        .flat_map(|&(loud, frames)| std::iter::repeat(if loud { LOUD } else { 0 }).take(frames * channels))
+        .collect()
+}
+
+fn sequential(samples: &[i16], spec: WavSpec, min_silence_len: usize) -> Vec<(usize, usize)> {
+    let channels = spec.channels as usize;
+    let mut detector = SilenceDetector::new(THRESHOLD, min_silence_len, spec);
+    let mut segments = Vec::new();
+    for (i, frame) in samples.chunks(channels).enumerate() {
+        segments.extend(detector.push(i * channels, SilenceDetector::frame_amplitude(frame, channels)));
+    }
+    segments.extend(detector.finish(samples.len()));
+    segments
+}
+
+/// Compares the parallel scan with a single sequential pass for every chunk size up to
+/// `max_chunk_frames`, so every run starts, ends and spans chunk boundaries somewhere.
+fn assert_matches_sequential(channels: u16, runs: &[(bool, usize)], min_silence_len: usize, max_chunk_frames: usize) {
+    let spec = spec(channels);
+    let samples = audio(channels as usize, runs);
+    let expected = sequential(&samples, spec, min_silence_len);
+    for chunk_frames in 1..=max_chunk_frames {
+        let parallel =
+            detect_silence_chunked(&samples, spec, THRESHOLD, min_silence_len, chunk_frames, &CancelToken::new(), |_| {});
+        assert_eq!(parallel.as_ref(), Some(&expected), "chunks of {} frames", chunk_frames);
+    }
+}
+
+#[test]
+fn runs_crossing_chunk_boundaries() {
+    let runs = [(true, 5), (false, 12), (true, 3), (false, 20), (true, 7), (false, 11), (true, 2)];
+    assert_matches_sequential(1, &runs, 10, 30);
+    assert_matches_sequential(2, &runs, 10, 30);
+}
+
+#[test]
+fn chunks_that_are_entirely_silent() {
+    // With small chunks the long run covers several chunks without a loud frame
+    let runs = [(true, 3), (false, 40), (true, 3), (false, 9), (true, 1)];
+    assert_matches_sequential(2, &runs, 10, 12);
+}
+
+#[test]
+fn trailing_run_at_end_of_file() {
+    assert_matches_sequential(1, &[(true, 10), (false, 15)], 10, 30);
+    assert_matches_sequential(1, &[(true, 10), (false, 9)], 10, 30);
+    assert_matches_sequential(2, &[(false, 25)], 10, 30);
+}
+
+#[test]
+fn runs_at_and_just_below_the_minimum_length() {
+    let runs = [(false, 10), (true, 4), (false, 9), (true, 4), (false, 10), (true, 4), (false, 9)];
+    assert_matches_sequential(1, &runs, 10, 30);
+    assert_matches_sequential(2, &runs, 10, 30);
+
+    let samples = audio(1, &runs);
+    let segments = detect_silence_chunked(&samples, spec(1), THRESHOLD, 10, 7, &CancelToken::new(), |_| {});
+    assert_eq!(segments, Some(vec![(0, 10), (27, 37)]));
+}
+
+#[test]
+fn default_chunking_matches_sequential() {
+    let runs: Vec<(bool, usize)> = (0..200).map(|i| (i % 2 == 0, 500 + i * 997 % 3000)).collect();
+    let spec = spec(2);
+    let samples = audio(2, &runs);
+    let parallel = detect_silence_parallel(&samples, spec, THRESHOLD, 1000, &CancelToken::new(), |_| {});
+    assert_eq!(parallel, Some(sequential(&samples, spec, 1000)));
+}
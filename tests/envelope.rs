@@ -0,0 +1,45 @@
+//! The live preview detects silence from a quantized envelope; it must agree with scanning the
+//! samples, even for frames right at the threshold.
+
+mod common;
+
+use common::spec;
+use solid_meme::audio::detect_silence_parallel;
+use solid_meme::envelope::Envelope;
+use solid_meme::job::CancelToken;
+use solid_meme::wav::SampleBuffer;
+use std::sync::Arc;
+
+fn assert_matches_detection(samples: Vec<i16>, channels: u16, threshold: f32) {
+    let spec = spec(channels);
+    let cancel = CancelToken::new();
+    let expected = detect_silence_parallel(&samples, spec, threshold, 3, &cancel, |_| {}).unwrap();
+    let envelope = Envelope::compute(Arc::new(SampleBuffer::Owned(samples)), channels as usize, &cancel).unwrap();
+    assert_eq!(envelope.detect(spec, threshold, 3, &cancel).unwrap(), expected, "threshold {}", threshold);
+}
+
+#[test]
+fn frames_at_the_threshold_match_detection() {
+    // Runs of frames just below, at and just above each threshold, which quantization to
+    // 1 / 65535 steps could otherwise put on the wrong side
+    for level in [1i16, 2, 327, 328, 1000, 3277, 16384, 32767] {
+        let threshold = level as f32 / i16::MAX as f32;
+        let samples: Vec<i16> = [level - 1, level, level.saturating_add(1), level - 1, level]
+            .into_iter()
+            .flat_map(|s| std::iter::repeat(s).take(4))
+            .collect();
+        assert_matches_detection(samples, 1, threshold);
+    }
+}
+
+#[test]
+fn stereo_frames_at_the_threshold_match_detection() {
+    for (left, right) in [(327, 328), (100, 555), (3276, 3277), (0, 655)] {
+        let threshold = (left as f32 / i16::MAX as f32 + right as f32 / i16::MAX as f32) / 2.0;
+        let samples: Vec<i16> = [(left, right), (left, right + 1), (left - 1, right), (-left, -right)]
+            .into_iter()
+            .flat_map(|(l, r)| [l, r, l, r, l, r, l, r])
+            .collect();
+        assert_matches_detection(samples, 2, threshold);
+    }
+}
@@ -0,0 +1,122 @@
+//! Peaks of a range cover exactly that range, the sidecar round-trips, and damaged or
+//! mismatched sidecars are rejected rather than trusted.
+
+mod common;
+
+use common::TempFile;
+use solid_meme::job::CancelToken;
+use solid_meme::peaks::PeakPyramid;
+
+const CHANNELS: usize = 2;
+const FRAMES: usize = 100_003;
+
+/// A stand-in WAV file, since the sidecar only checks its size and modification time, and a
+/// guard for the sidecar saved next to it.
+fn wav_file(name: &str) -> (TempFile, TempFile) {
+    let file = TempFile::new(name);
+    std::fs::write(file.path(), b"audio").unwrap();
+    let sidecar = TempFile::guard(PeakPyramid::sidecar_path(file.path()));
+    (file, sidecar)
+}
+
+fn samples() -> Vec<i16> {
+    (0..FRAMES * CHANNELS).map(|i| (i * 7919 % 65536) as u16 as i16).collect()
+}
+
+fn pyramid() -> PeakPyramid {
+    PeakPyramid::build(&samples(), CHANNELS, &CancelToken::new()).unwrap()
+}
+
+/// The value of `frame` in a lane: a channel, or the mixdown after the last channel.
+fn frame_value(samples: &[i16], lane: usize, frame: usize) -> i16 {
+    let frame = &samples[frame * CHANNELS..(frame + 1) * CHANNELS];
+    match frame.get(lane) {
+        Some(&s) => s,
+        None => (frame.iter().map(|&s| s as i32).sum::<i32>() / CHANNELS as i32) as i16,
+    }
+}
+
+#[test]
+fn range_matches_a_scan_of_exactly_that_range() {
+    let samples = samples();
+    let peaks = pyramid();
+    for lane in 0..=peaks.mixdown_lane() {
+        for (from, to) in [(0, FRAMES), (1, 257), (255, 769), (300, 70_001), (4096, 8192), (FRAMES - 1000, FRAMES)] {
+            let peak = peaks.range(lane, from, to, |frame| frame_value(&samples, lane, frame)).unwrap();
+            let values: Vec<i16> = (from..to).map(|frame| frame_value(&samples, lane, frame)).collect();
+            assert_eq!(peak.min, *values.iter().min().unwrap(), "lane {} {}..{}", lane, from, to);
+            assert_eq!(peak.max, *values.iter().max().unwrap(), "lane {} {}..{}", lane, from, to);
+            let mean_square = values.iter().map(|&s| (s as f64) * (s as f64)).sum::<f64>() / values.len() as f64;
+            assert!((peak.mean_square as f64 - mean_square).abs() <= mean_square * 1e-4, "lane {} {}..{}", lane, from, to);
+        }
+    }
+}
+
+#[test]
+fn a_spike_shows_up_in_one_column_only() {
+    // Silence with one spike, drawn in columns of 1000 frames that don't line up with blocks
+    let spike = 37_123;
+    let mut samples = vec![0i16; FRAMES];
+    samples[spike] = 20_000;
+    let peaks = PeakPyramid::build(&samples, 1, &CancelToken::new()).unwrap();
+    let lit: Vec<usize> = (0..FRAMES / 1000)
+        .filter(|&column| {
+            let peak = peaks.range(0, column * 1000, (column + 1) * 1000, |frame| samples[frame]).unwrap();
+            peak.max > 0
+        })
+        .collect();
+    assert_eq!(lit, [spike / 1000]);
+}
+
+#[test]
+fn sidecar_round_trips() {
+    let (file, _sidecar) = wav_file("peaks_round_trip");
+    let path = file.path();
+    let peaks = pyramid();
+    peaks.save(path).unwrap();
+    let loaded = PeakPyramid::load(path, FRAMES, CHANNELS).unwrap();
+
+    assert_eq!(loaded.mixdown_lane(), peaks.mixdown_lane());
+    for lane in 0..=peaks.mixdown_lane() {
+        for (from, to) in [(0, FRAMES), (10, 90_000), (5000, 5300), (FRAMES - 300, FRAMES)] {
+            assert_eq!(loaded.range(lane, from, to, |_| 0), peaks.range(lane, from, to, |_| 0));
+        }
+    }
+}
+
+#[test]
+fn sidecar_for_different_audio_is_rejected() {
+    let (file, _sidecar) = wav_file("peaks_mismatch");
+    let path = file.path();
+    pyramid().save(path).unwrap();
+    assert!(PeakPyramid::load(path, FRAMES - 1, CHANNELS).is_err());
+    assert!(PeakPyramid::load(path, FRAMES, 1).is_err());
+}
+
+#[test]
+fn truncated_sidecar_is_rejected() {
+    let (file, sidecar) = wav_file("peaks_truncated");
+    let path = file.path();
+    pyramid().save(path).unwrap();
+    let bytes = std::fs::read(sidecar.path()).unwrap();
+    for len in [0, 10, 48, 60, bytes.len() / 2, bytes.len() - 1] {
+        std::fs::write(sidecar.path(), &bytes[..len]).unwrap();
+        assert!(PeakPyramid::load(path, FRAMES, CHANNELS).is_err(), "truncated to {} bytes", len);
+    }
+}
+
+#[test]
+fn huge_counts_are_rejected_without_allocating() {
+    let (file, sidecar) = wav_file("peaks_huge");
+    let path = file.path();
+    pyramid().save(path).unwrap();
+    let bytes = std::fs::read(sidecar.path()).unwrap();
+    // The frame count follows the magic, version, size and modification time, and the first
+    // level's peak count follows the channel, lane and level counts
+    for offset in [24, 44] {
+        let mut corrupt = bytes.clone();
+        corrupt[offset..offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(sidecar.path(), &corrupt).unwrap();
+        assert!(PeakPyramid::load(path, FRAMES, CHANNELS).is_err());
+    }
+}
@@ -0,0 +1,315 @@
+//! Drives the playback callback the way an audio device would, without opening one.
+
+mod common;
+
+use hound::WavSpec;
+use solid_meme::playback::{transport_channel, Renderer, TransportControl};
+use solid_meme::wav::SampleBuffer;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Counts heap operations made by the current thread while `COUNTING` is set.
+struct CountingAllocator;
+
+static HEAP_OPERATIONS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn count() {
+    if COUNTING.try_with(|c| c.get()).unwrap_or(false) {
+        HEAP_OPERATIONS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        count();
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn spec(channels: u16, sample_rate: u32) -> WavSpec {
+    WavSpec { sample_rate, ..common::spec(channels) }
+}
+
+fn buffer(samples: Vec<i16>) -> Arc<SampleBuffer> {
+    Arc::new(SampleBuffer::Owned(samples))
+}
+
+/// A renderer playing the whole of `samples` from the start.
+fn renderer(samples: Vec<i16>, spec: WavSpec, device_channels: usize, device_rate: u32) -> (Renderer, TransportControl) {
+    let (control, renderer) = transport_channel(spec, device_channels, device_rate);
+    let len = samples.len();
+    control.load(buffer(samples), 0..len, 0);
+    control.play();
+    (renderer, control)
+}
+
+/// Calls `render` with `buffer_len` samples at a time until `total` samples have been produced.
+fn drive(renderer: &mut Renderer, buffer_len: usize, total: usize) -> Vec<f32> {
+    let mut output = Vec::new();
+    let mut buffer = vec![0.0; buffer_len];
+    while output.len() < total {
+        renderer.render(&mut buffer);
+        output.extend_from_slice(&buffer);
+    }
+    output.truncate(total);
+    output
+}
+
+fn to_f32(sample: i16) -> f32 {
+    sample as f32 / i16::MAX as f32
+}
+
+#[test]
+fn plays_every_sample_in_order_then_silence() {
+    let samples: Vec<i16> = (0..1000).map(|i| (i * 31 % 20000) as i16).collect();
+    let (mut renderer, control) = renderer(samples.clone(), spec(2, 44100), 2, 44100);
+
+    let output = drive(&mut renderer, 128, 1200);
+    let expected: Vec<f32> = samples.iter().map(|&s| to_f32(s)).collect();
+    assert_eq!(&output[..1000], &expected[..]);
+    assert!(output[1000..].iter().all(|&s| s == 0.0));
+    assert_eq!(control.position(), 1000);
+    assert!(control.take_finished());
+    assert!(!control.take_finished());
+}
+
+#[test]
+fn position_advances_with_each_buffer() {
+    let (mut renderer, control) = renderer(vec![1; 2000], spec(2, 48000), 2, 48000);
+    let mut buffer = vec![0.0; 256];
+    renderer.render(&mut buffer);
+    assert_eq!(control.position(), 256);
+    renderer.render(&mut buffer);
+    assert_eq!(control.position(), 512);
+    assert!(!control.take_finished());
+}
+
+#[test]
+fn mono_file_is_duplicated_to_stereo_device() {
+    let samples = vec![100, -200, 300];
+    let (mut renderer, _) = renderer(samples, spec(1, 44100), 2, 44100);
+    let output = drive(&mut renderer, 6, 6);
+    assert_eq!(output, [100, 100, -200, -200, 300, 300].map(to_f32));
+}
+
+#[test]
+fn stereo_file_is_averaged_on_mono_device() {
+    let (mut renderer, _) = renderer(vec![100, 300, -50, -150], spec(2, 44100), 1, 44100);
+    let output = drive(&mut renderer, 2, 2);
+    assert_eq!(output, [(to_f32(100) + to_f32(300)) / 2.0, (to_f32(-50) + to_f32(-150)) / 2.0]);
+}
+
+#[test]
+fn seek_takes_effect_on_next_buffer() {
+    let samples: Vec<i16> = (0..100).collect();
+    let (mut renderer, control) = renderer(samples, spec(1, 44100), 1, 44100);
+    drive(&mut renderer, 10, 10);
+    control.seek(50);
+    assert_eq!(control.position(), 50);
+    let output = drive(&mut renderer, 10, 10);
+    assert_eq!(output, (50..60).map(to_f32).collect::<Vec<_>>());
+    assert_eq!(control.position(), 60);
+}
+
+#[test]
+fn resampled_playback_lasts_as_long_as_the_file() {
+    let frames = 44100;
+    let samples: Vec<i16> = (0..frames).map(|i| ((i as f32 * 0.05).sin() * 10000.0) as i16).collect();
+    let (mut renderer, control) = renderer(samples, spec(1, 44100), 1, 48000);
+
+    let mut played = 0;
+    let mut buffer = vec![0.0; 512];
+    while !control.take_finished() {
+        renderer.render(&mut buffer);
+        played += buffer.len();
+        assert!(played < 60000, "playback did not finish");
+    }
+    // One second of audio is 48000 frames at the device rate, give or take the last buffer
+    assert!((48000..48000 + 512).contains(&played), "played {} frames", played);
+    assert_eq!(control.position(), frames);
+}
+
+#[test]
+fn render_does_not_touch_the_heap() {
+    for (file_rate, device_rate) in [(44100, 44100), (44100, 48000)] {
+        let (mut renderer, control) = renderer(vec![1000; 20000], spec(2, file_rate), 2, device_rate);
+        let mut output = vec![0.0; 512];
+        let other = buffer(vec![-1000; 20000]);
+
+        COUNTING.with(|c| c.set(true));
+        for i in 0..40 {
+            if i == 10 {
+                control.seek(4000);
+            }
+            if i == 20 {
+                control.load(Arc::clone(&other), 0..20000, 8000);
+            }
+            renderer.render(&mut output);
+        }
+        COUNTING.with(|c| c.set(false));
+
+        assert_eq!(HEAP_OPERATIONS.load(Ordering::Relaxed), 0);
+    }
+}
+
+#[test]
+fn nothing_plays_until_loaded_and_started() {
+    let (control, mut renderer) = transport_channel(spec(1, 44100), 1, 44100);
+    assert!(drive(&mut renderer, 8, 8).iter().all(|&s| s == 0.0));
+    control.load(buffer(vec![1000; 100]), 0..100, 0);
+    assert!(drive(&mut renderer, 8, 8).iter().all(|&s| s == 0.0));
+    assert_eq!(control.position(), 0);
+    control.play();
+    assert_eq!(drive(&mut renderer, 8, 8), vec![to_f32(1000); 8]);
+}
+
+#[test]
+fn pause_keeps_the_position_and_plays_silence() {
+    let samples: Vec<i16> = (0..100).collect();
+    let (mut renderer, control) = renderer(samples, spec(1, 44100), 1, 44100);
+    drive(&mut renderer, 10, 20);
+    control.pause();
+    assert!(drive(&mut renderer, 10, 30).iter().all(|&s| s == 0.0));
+    assert_eq!(control.position(), 20);
+    control.play();
+    assert_eq!(drive(&mut renderer, 10, 10), (20..30).map(to_f32).collect::<Vec<_>>());
+    assert!(!control.take_finished());
+}
+
+#[test]
+fn switching_source_takes_effect_at_the_next_buffer() {
+    let original = buffer((0..100).collect());
+    let (control, mut renderer) = transport_channel(spec(1, 44100), 1, 44100);
+    control.load(Arc::clone(&original), 0..100, 0);
+    control.play();
+    assert_eq!(drive(&mut renderer, 10, 10), (0..10).map(to_f32).collect::<Vec<_>>());
+
+    // Loading keeps playing, from the given position of the new buffer
+    control.load(buffer((1000..1100).collect()), 0..100, 40);
+    assert_eq!(control.position(), 40);
+    assert_eq!(drive(&mut renderer, 10, 10), (1040..1050).map(to_f32).collect::<Vec<_>>());
+    assert_eq!(control.position(), 50);
+
+    // The callback hands the replaced buffer back instead of freeing it
+    assert_eq!(Arc::strong_count(&original), 2);
+    control.collect_retired();
+    assert_eq!(Arc::strong_count(&original), 1);
+}
+
+#[test]
+fn playback_stops_at_the_end_of_the_range() {
+    let samples: Vec<i16> = (0..200).collect();
+    let (control, mut renderer) = transport_channel(spec(2, 44100), 2, 44100);
+    control.load(buffer(samples), 40..100, 40);
+    control.play();
+
+    let output = drive(&mut renderer, 16, 96);
+    assert_eq!(&output[..60], &(40..100).map(to_f32).collect::<Vec<_>>()[..]);
+    assert!(output[60..].iter().all(|&s| s == 0.0));
+    assert_eq!(control.position(), 100);
+    assert!(control.take_finished());
+
+    // Playing again starts over from the start of the range
+    control.play();
+    assert_eq!(drive(&mut renderer, 4, 4), (40..44).map(to_f32).collect::<Vec<_>>());
+}
+
+#[test]
+fn seek_outside_the_range_is_clamped() {
+    let samples: Vec<i16> = (0..100).collect();
+    let (control, mut renderer) = transport_channel(spec(1, 44100), 1, 44100);
+    control.load(buffer(samples), 20..50, 20);
+    control.play();
+    control.seek(5);
+    assert_eq!(drive(&mut renderer, 5, 5), (20..25).map(to_f32).collect::<Vec<_>>());
+}
+
+#[test]
+fn seeks_while_paused_do_not_crowd_out_play() {
+    let samples: Vec<i16> = (0..1000).collect();
+    let (mut renderer, control) = renderer(samples, spec(1, 44100), 1, 44100);
+    drive(&mut renderer, 10, 10);
+    control.pause();
+    // A paused device does not call back, so nothing drains the queue in between
+    for position in 0..500 {
+        control.seek(position);
+    }
+    control.play();
+    assert_eq!(drive(&mut renderer, 10, 10), (499..509).map(to_f32).collect::<Vec<_>>());
+}
+
+#[test]
+fn load_overrides_an_earlier_seek() {
+    let (mut renderer, control) = renderer((0..100).collect(), spec(1, 44100), 1, 44100);
+    control.seek(50);
+    control.load(buffer((1000..1100).collect()), 0..100, 20);
+    assert_eq!(drive(&mut renderer, 5, 5), (1020..1025).map(to_f32).collect::<Vec<_>>());
+}
+
+#[test]
+fn replaced_buffers_are_never_freed_by_the_callback() {
+    let (control, mut renderer) = transport_channel(spec(1, 44100), 1, 44100);
+    control.play();
+    let buffers: Vec<_> = (0..100).map(|i| buffer(vec![i; 1000])).collect();
+    let mut output = vec![0.0; 4];
+    // Without the UI collecting, the retired queue fills up; the callback keeps the next
+    // replaced buffer and holds further loads back instead of freeing it
+    let mut playing = 0;
+    for samples in &buffers {
+        control.load(Arc::clone(samples), 0..1000, 0);
+        renderer.render(&mut output);
+        playing = (output[0] * i16::MAX as f32).round() as usize;
+    }
+    assert!(playing < 99, "every load was applied");
+    assert!(buffers.iter().all(|b| Arc::strong_count(b) == 2));
+
+    // Once collected, the held back loads follow and the replaced buffers are freed on this thread
+    while playing < 99 {
+        control.collect_retired();
+        renderer.render(&mut output);
+        playing = (output[0] * i16::MAX as f32).round() as usize;
+    }
+    control.collect_retired();
+    renderer.render(&mut output);
+    control.collect_retired();
+    assert!(buffers[..99].iter().all(|b| Arc::strong_count(b) == 1));
+    assert_eq!(Arc::strong_count(&buffers[99]), 2);
+}
+
+#[test]
+fn renderer_for_a_reopened_device_keeps_playing() {
+    let samples: Vec<i16> = (0..100).collect();
+    let (mut old, control) = renderer(samples.clone(), spec(1, 44100), 1, 44100);
+    assert_eq!(drive(&mut old, 10, 10), (0..10).map(to_f32).collect::<Vec<_>>());
+
+    // Switching devices builds a new, stopped renderer, told where the old one was
+    let (new_control, mut new) = transport_channel(spec(1, 44100), 2, 44100);
+    new_control.resume(buffer(samples), 0..100, control.position(), true);
+    assert_eq!(drive(&mut new, 4, 4), [10, 10, 11, 11].map(to_f32));
+
+    // A paused transport stays quiet until played
+    let (paused_control, mut paused) = transport_channel(spec(1, 44100), 1, 44100);
+    paused_control.resume(buffer((0..100).collect()), 0..100, 30, false);
+    assert!(drive(&mut paused, 4, 4).iter().all(|&s| s == 0.0));
+    paused_control.play();
+    assert_eq!(drive(&mut paused, 2, 2), [30, 31].map(to_f32));
+}
@@ -0,0 +1,80 @@
+//! Export resampling must keep the length, level and pitch of the audio.
+
+use solid_meme::job::CancelToken;
+use solid_meme::resample::resample;
+use std::f64::consts::TAU;
+
+const RATES: [(u32, u32); 2] = [(44100, 48000), (48000, 44100)];
+
+fn convert(samples: &[i16], channels: usize, from: u32, to: u32) -> Vec<i16> {
+    resample(samples, channels, from, to, &CancelToken::new(), |_| {}).unwrap()
+}
+
+fn sine(frequency: f64, rate: u32, frames: usize, channels: usize) -> Vec<i16> {
+    (0..frames * channels)
+        .map(|i| (16000.0 * (TAU * frequency * (i / channels) as f64 / rate as f64).sin()).round() as i16)
+        .collect()
+}
+
+/// Frames away from both ends, where the filter sees only input samples.
+fn middle(samples: &[i16], channels: usize) -> Vec<f64> {
+    let frames = samples.len() / channels;
+    samples[frames / 4 * channels..frames * 3 / 4 * channels].iter().step_by(channels).map(|&s| s as f64).collect()
+}
+
+#[test]
+fn output_length_follows_the_rate_ratio() {
+    for (from, to) in RATES {
+        for channels in [1, 2] {
+            let output = convert(&vec![0; 10_000 * channels], channels, from, to);
+            let expected = (10_000u64 * to as u64).div_ceil(from as u64) as usize;
+            assert_eq!(output.len(), expected * channels, "{} -> {} Hz, {} channels", from, to, channels);
+        }
+    }
+}
+
+#[test]
+fn same_rate_is_unchanged() {
+    let samples = sine(1000.0, 48000, 1000, 2);
+    assert_eq!(convert(&samples, 2, 48000, 48000), samples);
+}
+
+#[test]
+fn dc_passes_at_unity_gain() {
+    for (from, to) in RATES {
+        let output = convert(&vec![10_000; 20_000], 1, from, to);
+        for &s in &middle(&output, 1) {
+            assert!((s - 10_000.0).abs() <= 20.0, "{} -> {} Hz: {}", from, to, s);
+        }
+    }
+}
+
+#[test]
+fn sine_keeps_its_frequency_and_level() {
+    for (from, to) in RATES {
+        for frequency in [440.0, 5000.0] {
+            let output = convert(&sine(frequency, from, from as usize, 2), 2, from, to);
+            let middle = middle(&output, 2);
+            // Project onto a sine and cosine at the expected frequency; what is left over is
+            // distortion or a shifted pitch
+            let (mut re, mut im) = (0.0, 0.0);
+            for (i, &s) in middle.iter().enumerate() {
+                let phase = TAU * frequency * i as f64 / to as f64;
+                re += s * phase.cos();
+                im += s * phase.sin();
+            }
+            let amplitude = 2.0 * (re * re + im * im).sqrt() / middle.len() as f64;
+            let power: f64 = middle.iter().map(|s| s * s).sum::<f64>() / middle.len() as f64;
+            let residual = power - amplitude * amplitude / 2.0;
+            assert!((amplitude - 16000.0).abs() < 50.0, "{} -> {} Hz, {} Hz: amplitude {}", from, to, frequency, amplitude);
+            assert!(residual < power * 1e-4, "{} -> {} Hz, {} Hz: residual {} of {}", from, to, frequency, residual, power);
+        }
+    }
+}
+
+#[test]
+fn cancelled_conversion_returns_none() {
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    assert!(resample(&vec![0; 10_000], 1, 44100, 48000, &cancel, |_| {}).is_none());
+}
@@ -0,0 +1,63 @@
+//! Spectrogram columns are centred on their part of the range, so an image of part of the file
+//! lines up with the same part of an image of the whole file.
+
+use solid_meme::job::CancelToken;
+use solid_meme::spectrogram::{compute_spectrogram, ColorMap, SpectrogramSettings, WindowFunction};
+use std::ops::Range;
+
+const FRAMES: usize = 8192;
+
+fn settings() -> SpectrogramSettings {
+    SpectrogramSettings {
+        fft_size: 256,
+        window: WindowFunction::Hann,
+        color_map: ColorMap::Heat,
+    }
+}
+
+fn impulse(at: usize) -> Vec<i16> {
+    let mut samples = vec![0i16; FRAMES];
+    samples[at] = i16::MAX;
+    samples
+}
+
+/// Total brightness of each column of the image.
+fn column_levels(samples: &[i16], frames: Range<usize>, columns: usize) -> Vec<u32> {
+    let image = compute_spectrogram(samples, 1, settings(), frames, columns, &CancelToken::new(), |_| {}).unwrap();
+    let [width, height] = image.size;
+    (0..width)
+        .map(|x| {
+            (0..height)
+                .map(|y| {
+                    let c = image.pixels[y * width + x];
+                    c.r() as u32 + c.g() as u32 + c.b() as u32
+                })
+                .sum()
+        })
+        .collect()
+}
+
+fn brightest(levels: &[u32]) -> usize {
+    (0..levels.len()).max_by_key(|&i| levels[i]).unwrap()
+}
+
+#[test]
+fn impulse_is_brightest_in_the_column_centred_nearest_to_it() {
+    // Columns of 128 frames from 2048; column 7 is centred on frame 3008
+    let levels = column_levels(&impulse(3000), 2048..4096, 16);
+    assert_eq!(levels.len(), 16);
+    assert_eq!(brightest(&levels), 7);
+}
+
+#[test]
+fn part_of_the_file_matches_the_same_columns_of_the_whole_file() {
+    let samples = impulse(3000);
+    let whole = column_levels(&samples, 0..FRAMES, FRAMES / 128);
+    let part = column_levels(&samples, 2048..4096, 16);
+    assert_eq!(part, whole[16..32]);
+}
+
+#[test]
+fn columns_are_limited_to_the_frames_in_the_range() {
+    assert_eq!(column_levels(&impulse(100), 90..110, 1000).len(), 20);
+}
@@ -0,0 +1,93 @@
+//! Streaming silence removal must write the same audio as removing silence in memory.
+
+mod common;
+
+use common::{spec, write_wav, TempFile};
+use hound::{WavReader, WavSpec};
+use solid_meme::audio::{detect_silence_parallel, non_silent_regions};
+use solid_meme::job::CancelToken;
+use solid_meme::stream::remove_silence_streaming;
+
+const THRESHOLD: f32 = 0.01;
+/// At 1 kHz this is also the number of frames, so the held-back silence never exceeds 50 frames.
+const MIN_SILENCE_MS: usize = 50;
+
+/// Interleaved audio made of alternating loud and silent runs of the given frame counts.
+fn audio(channels: usize, runs: &[(bool, usize)]) -> Vec<i16> {
+    let mut samples = Vec::new();
+    for &(loud, frames) in runs {
+        for i in 0..frames * channels {
+            samples.push(if loud { 3000 + (i % 7) as i16 * 100 } else { (i % 3) as i16 });
+        }
+    }
+    samples
+}
+
+/// What "Remove Silence" produces for the same file.
+fn remove_in_memory(samples: &[i16], spec: WavSpec) -> (Vec<(usize, usize)>, Vec<i16>) {
+    let segments = detect_silence_parallel(samples, spec, THRESHOLD, MIN_SILENCE_MS, &CancelToken::new(), |_| {}).unwrap();
+    let kept = non_silent_regions(&segments, samples.len())
+        .into_iter()
+        .flat_map(|(start, end)| samples[start..end].iter().copied())
+        .collect();
+    (segments, kept)
+}
+
+fn assert_streaming_matches(name: &str, channels: u16, runs: &[(bool, usize)]) {
+    let spec = spec(channels);
+    let samples = audio(channels as usize, runs);
+    let input = write_wav(&format!("{}_in", name), spec, &samples);
+    let output = TempFile::new(&format!("{}_out", name));
+
+    let summary =
+        remove_silence_streaming(input.path(), output.path(), THRESHOLD, MIN_SILENCE_MS, &CancelToken::new(), |_| {}).unwrap();
+    let written: Vec<i16> = WavReader::open(output.path()).unwrap().samples().map(|s| s.unwrap()).collect();
+
+    let (segments, kept) = remove_in_memory(&samples, spec);
+    assert_eq!(summary.silence_segments, segments);
+    assert_eq!(summary.samples_read, samples.len());
+    assert_eq!(summary.samples_written, kept.len());
+    assert!(written == kept, "streamed output differs from in-memory removal");
+}
+
+#[test]
+fn runs_around_the_minimum_length() {
+    // Runs one frame shorter than, equal to and longer than what is held back
+    let runs = [(true, 100), (false, 49), (true, 10), (false, 50), (true, 10), (false, 51), (true, 10), (false, 500), (true, 100)];
+    assert_streaming_matches("minimum", 1, &runs);
+    assert_streaming_matches("minimum_stereo", 2, &runs);
+}
+
+#[test]
+fn leading_and_trailing_silence() {
+    assert_streaming_matches("edges_removed", 2, &[(false, 300), (true, 100), (false, 300)]);
+    assert_streaming_matches("edges_kept", 2, &[(false, 20), (true, 100), (false, 20)]);
+}
+
+#[test]
+fn silence_longer_than_a_read_chunk() {
+    // Chunks are 64k frames, so these runs cross chunk boundaries
+    let runs = [(true, 60_000), (false, 10_000), (true, 1000), (false, 100_000), (true, 5), (false, 30), (true, 70_000)];
+    assert_streaming_matches("long", 2, &runs);
+}
+
+#[test]
+fn all_silent_file_is_emptied() {
+    assert_streaming_matches("silent", 1, &[(false, 1000)]);
+}
+
+#[test]
+fn cancelling_deletes_the_partial_output() {
+    let input = write_wav("cancel_in", spec(1), &audio(1, &[(true, 200_000)]));
+    let output = TempFile::new("cancel_out");
+
+    // Cancel once the first chunk has been written
+    let cancel = CancelToken::new();
+    let result = remove_silence_streaming(input.path(), output.path(), THRESHOLD, MIN_SILENCE_MS, &cancel, |_| cancel.cancel());
+
+    let Err(hound::Error::IoError(err)) = result else {
+        panic!("expected an I/O error");
+    };
+    assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+    assert!(!output.path().exists());
+}
@@ -0,0 +1,140 @@
+//! Parses, retimes and writes SRT and WebVTT subtitles.
+
+mod common;
+
+use common::spec;
+use solid_meme::subtitle::{SubtitleFormat, Subtitles};
+use solid_meme::timeline::TimelineMap;
+
+const SRT: &str = "1
+00:00:01,000 --> 00:00:02,500
+Hello
+
+2
+00:00:03,000 --> 00:00:04,000
+Two
+lines
+
+";
+
+const VTT: &str = "WEBVTT
+
+STYLE
+::cue { color: white }
+
+intro
+00:00:01.000 --> 00:00:02.500 align:start
+Hello
+
+NOTE between cues
+
+00:00:03.000 --> 00:00:04.000
+Second
+
+NOTE at the end
+
+";
+
+fn times(subtitles: &Subtitles) -> Vec<(u64, u64)> {
+    subtitles.cues.iter().map(|cue| (cue.start_ms, cue.end_ms)).collect()
+}
+
+#[test]
+fn srt_round_trips() {
+    let subtitles = Subtitles::parse(SRT, SubtitleFormat::Srt).unwrap();
+    assert_eq!(times(&subtitles), [(1000, 2500), (3000, 4000)]);
+    assert_eq!(subtitles.cues[1].text, "Two\nlines");
+    assert_eq!(subtitles.to_text(), SRT);
+}
+
+#[test]
+fn srt_with_crlf_and_bom_parses() {
+    let content = format!("\u{feff}{}", SRT.replace('\n', "\r\n"));
+    let subtitles = Subtitles::parse(&content, SubtitleFormat::Srt).unwrap();
+    assert_eq!(subtitles.to_text(), SRT);
+}
+
+#[test]
+fn vtt_round_trips_with_notes_and_styles() {
+    let subtitles = Subtitles::parse(VTT, SubtitleFormat::Vtt).unwrap();
+    assert_eq!(subtitles.header, ["WEBVTT", "STYLE\n::cue { color: white }"]);
+    assert_eq!(subtitles.cues[0].identifier.as_deref(), Some("intro"));
+    assert_eq!(subtitles.cues[0].settings, "align:start");
+    assert_eq!(subtitles.cues[1].blocks, ["NOTE between cues"]);
+    assert_eq!(subtitles.trailer, ["NOTE at the end"]);
+    assert_eq!(subtitles.to_text(), VTT);
+}
+
+#[test]
+fn vtt_without_header_is_rejected() {
+    assert!(Subtitles::parse("00:01.000 --> 00:02.000\nHi\n", SubtitleFormat::Vtt).is_err());
+}
+
+#[test]
+fn invalid_timestamp_is_rejected() {
+    assert!(Subtitles::parse("1\n00:00:01 --> 00:00:02,000\nHi\n", SubtitleFormat::Srt).is_err());
+}
+
+#[test]
+fn retime_without_removals_changes_nothing() {
+    let subtitles = Subtitles::parse(SRT, SubtitleFormat::Srt).unwrap();
+    assert_eq!(subtitles.retime(&TimelineMap::identity(10_000), spec(1)), subtitles);
+}
+
+#[test]
+fn retime_trims_and_drops_cues_at_cuts() {
+    let content = "1
+00:00:01,500 --> 00:00:02,500
+Ends inside the cut
+
+2
+00:00:02,200 --> 00:00:02,800
+Entirely removed
+
+3
+00:00:02,900 --> 00:00:03,500
+Starts inside the cut
+
+4
+00:00:01,000 --> 00:00:03,500
+Spans the cut
+
+5
+00:00:05,000 --> 00:00:06,000
+After the cut
+
+";
+    let subtitles = Subtitles::parse(content, SubtitleFormat::Srt).unwrap();
+    let timeline = TimelineMap::from_removed(&[(2000, 3000)], 10_000);
+    let retimed = subtitles.retime(&timeline, spec(1));
+
+    assert_eq!(times(&retimed), [(1500, 2000), (2000, 2500), (1000, 2500), (4000, 5000)]);
+    let text: Vec<&str> = retimed.cues.iter().map(|cue| cue.text.as_str()).collect();
+    assert_eq!(text, ["Ends inside the cut", "Starts inside the cut", "Spans the cut", "After the cut"]);
+    // Sequence numbers stay consecutive after the dropped cue
+    assert!(retimed.to_text().contains("4\n00:00:04,000 --> 00:00:05,000\nAfter the cut"));
+}
+
+#[test]
+fn notes_before_a_dropped_cue_are_kept() {
+    let content = "WEBVTT
+
+00:00:00.000 --> 00:00:01.000
+First
+
+NOTE before the removed cue
+
+00:00:02.200 --> 00:00:02.800
+Removed
+
+00:00:04.000 --> 00:00:05.000
+Kept
+
+";
+    let subtitles = Subtitles::parse(content, SubtitleFormat::Vtt).unwrap();
+    let timeline = TimelineMap::from_removed(&[(2000, 3000)], 10_000);
+    let retimed = subtitles.retime(&timeline, spec(1));
+    assert_eq!(times(&retimed), [(0, 1000), (3000, 4000)]);
+    assert_eq!(retimed.cues[1].blocks, ["NOTE before the removed cue"]);
+    assert!(retimed.to_text().ends_with("NOTE before the removed cue\n\n00:00:03.000 --> 00:00:04.000\nKept\n\n"));
+}
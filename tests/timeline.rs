@@ -0,0 +1,142 @@
+//! Maps positions between the original and processed timelines at the places silence removal
+//! makes awkward: before the first kept region, inside removed gaps, on region edges and at the
+//! end of the file.
+
+use solid_meme::audio::PlaybackSource;
+use solid_meme::timeline::{KeptRegion, TimelineMap};
+
+/// 100 samples with 0..10, 40..50 and 90..100 removed, keeping 10..40 and 50..90.
+fn map() -> TimelineMap {
+    TimelineMap::from_removed(&[(0, 10), (40, 50), (90, 100)], 100)
+}
+
+#[test]
+fn kept_regions_are_packed_back_to_back() {
+    assert_eq!(
+        map().regions,
+        [
+            KeptRegion { processed_start: 0, original_start: 10, len: 30 },
+            KeptRegion { processed_start: 30, original_start: 50, len: 40 },
+        ]
+    );
+}
+
+#[test]
+fn identity_maps_every_index_to_itself() {
+    let map = TimelineMap::identity(50);
+    for idx in [0, 1, 25, 49, 50] {
+        assert_eq!(map.original_to_processed(idx), idx);
+        assert_eq!(map.processed_to_original(idx), idx);
+        assert!(!map.is_removed(idx));
+    }
+}
+
+#[test]
+fn removed_leading_silence_maps_to_the_start() {
+    let map = map();
+    for idx in 0..10 {
+        assert!(map.is_removed(idx));
+        assert_eq!(map.original_to_processed(idx), 0);
+    }
+    assert_eq!(map.processed_to_original(0), 10);
+}
+
+#[test]
+fn gap_maps_to_where_playback_resumes() {
+    let map = map();
+    for idx in 40..50 {
+        assert!(map.is_removed(idx));
+        assert_eq!(map.original_to_processed(idx), 30);
+    }
+    assert_eq!(map.processed_to_original(30), 50);
+}
+
+#[test]
+fn region_edges_map_exactly() {
+    let map = map();
+    // First and last sample of each kept region
+    for (original, processed) in [(10, 0), (39, 29), (50, 30), (89, 69)] {
+        assert!(!map.is_removed(original));
+        assert_eq!(map.original_to_processed(original), processed);
+        assert_eq!(map.processed_to_original(processed), original);
+    }
+}
+
+#[test]
+fn round_trip_through_kept_audio() {
+    let map = map();
+    for idx in (10..40).chain(50..90) {
+        assert_eq!(map.processed_to_original(map.original_to_processed(idx)), idx);
+    }
+}
+
+#[test]
+fn end_of_file_maps_to_end_of_file() {
+    let map = map();
+    // Trailing silence was removed, so it maps to the end of the processed audio
+    for idx in 90..100 {
+        assert!(map.is_removed(idx));
+        assert_eq!(map.original_to_processed(idx), 70);
+    }
+    assert_eq!(map.original_to_processed(100), 70);
+    assert_eq!(map.processed_to_original(70), 100);
+    assert_eq!(map.processed_to_original(1000), 100);
+    assert!(!map.is_removed(100));
+
+    let kept_tail = TimelineMap::from_removed(&[(40, 50)], 100);
+    assert_eq!(kept_tail.original_to_processed(100), 90);
+    assert_eq!(kept_tail.processed_to_original(90), 100);
+    assert!(!kept_tail.is_removed(99));
+    assert!(!kept_tail.is_removed(100));
+}
+
+#[test]
+fn empty_file_maps_to_zero() {
+    let map = TimelineMap::identity(0);
+    assert_eq!(map.original_to_processed(0), 0);
+    assert_eq!(map.processed_to_original(0), 0);
+    assert!(!map.is_removed(0));
+}
+
+#[test]
+fn positions_in_kept_audio_map_both_ways_between_waveforms() {
+    use PlaybackSource::{Processed, Raw};
+    let map = map();
+    for original in (10..40).chain(50..90) {
+        let processed = map.map_position(Raw, Processed, original);
+        assert_eq!(processed, map.original_to_processed(original));
+        assert_eq!(map.map_position(Processed, Raw, processed), original);
+        assert_eq!(map.map_position(Raw, Raw, original), original);
+        assert_eq!(map.map_position(Processed, Processed, processed), processed);
+    }
+}
+
+#[test]
+fn switching_inside_removed_silence_lands_where_the_processed_audio_resumes() {
+    use PlaybackSource::{Processed, Raw};
+    let map = map();
+    for original in 40..50 {
+        assert_eq!(map.map_position(Raw, Processed, original), 30);
+    }
+    // Switching back lands on the first kept sample after the cut, not inside it
+    assert_eq!(map.map_position(Processed, Raw, 30), 50);
+    for original in 0..10 {
+        assert_eq!(map.map_position(Raw, Processed, original), 0);
+    }
+    assert_eq!(map.map_position(Processed, Raw, 0), 10);
+}
+
+#[test]
+fn switching_at_the_end_of_the_file_stays_at_the_end() {
+    use PlaybackSource::{Processed, Raw};
+    let map = map();
+    // 90..100 is trailing silence, so the processed audio ends at 70
+    for original in 90..=100 {
+        assert_eq!(map.map_position(Raw, Processed, original), 70);
+    }
+    assert_eq!(map.map_position(Processed, Raw, 70), 100);
+    // A file without trailing silence ends at the same moment in both
+    let map = TimelineMap::from_removed(&[(40, 50)], 100);
+    assert_eq!(map.map_position(Raw, Processed, 100), 90);
+    assert_eq!(map.map_position(Processed, Raw, 90), 100);
+}
@@ -0,0 +1,62 @@
+//! Zooming and panning must stay smooth at the deepest zoom of a long file, where the scroll
+//! position counts more pixels than an `f32` resolves.
+
+use solid_meme::app::WaveformView;
+
+/// One hour of 48 kHz stereo.
+const TOTAL: f64 = 3600.0 * 48000.0 * 2.0;
+const CHANNELS: f64 = 2.0;
+const WIDTH: f32 = 1000.0;
+
+/// Zoomed all the way in, scrolled to the last minute of the file.
+fn deep_view() -> WaveformView {
+    let mut view = WaveformView::default();
+    view.zoom_at(f64::INFINITY, 0.0, TOTAL, CHANNELS, WIDTH);
+    view.center_on(TOTAL - 60.0 * 48000.0 * 2.0, TOTAL, WIDTH);
+    view
+}
+
+#[test]
+fn dragging_by_a_pixel_moves_the_view_by_a_pixel() {
+    let mut view = deep_view();
+    assert!(view.samples_per_pixel(TOTAL, WIDTH) < 1.0, "not zoomed past one sample per pixel");
+    let before = view.sample_at(0.0, TOTAL, WIDTH);
+    for _ in 0..10 {
+        view.pan(0.5, WIDTH);
+    }
+    let moved = view.sample_at(0.0, TOTAL, WIDTH) - before;
+    let expected = 5.0 * view.samples_per_pixel(TOTAL, WIDTH);
+    assert!((moved - expected).abs() < expected * 1e-3, "moved {} samples, expected {}", moved, expected);
+}
+
+#[test]
+fn zooming_keeps_the_sample_under_the_pointer() {
+    let mut view = deep_view();
+    view.zoom_at(0.5, 0.0, TOTAL, CHANNELS, WIDTH);
+    for anchor_x in [0.0, 137.5, 999.0] {
+        let mut zoomed = view;
+        let before = zoomed.sample_at(anchor_x, TOTAL, WIDTH);
+        zoomed.zoom_at(1.25, anchor_x, TOTAL, CHANNELS, WIDTH);
+        let after = zoomed.sample_at(anchor_x, TOTAL, WIDTH);
+        assert!((after - before).abs() < 0.01, "anchor {}: {} -> {}", anchor_x, before, after);
+    }
+}
+
+#[test]
+fn sample_and_pixel_mappings_are_inverse() {
+    let view = deep_view();
+    for x in [0.0, 0.25, 500.0, 999.75] {
+        let sample = view.sample_at(x, TOTAL, WIDTH);
+        assert!((view.x_of(sample, TOTAL, WIDTH) - x).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn deepest_zoom_is_capped_for_very_long_files() {
+    let mut view = WaveformView::default();
+    let total = 1e15;
+    view.zoom_at(f64::INFINITY, 0.0, total, 1.0, WIDTH);
+    assert!(view.zoom.is_finite());
+    // The whole file still spans few enough pixels for the offset to resolve a fraction of one
+    assert!(view.zoom * WIDTH as f64 <= (1u64 << 40) as f64);
+}
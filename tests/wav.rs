@@ -0,0 +1,38 @@
+//! Memory mapping accepts 16-bit PCM files and rejects headers that would break later code.
+
+mod common;
+
+use common::{spec, write_wav};
+use solid_meme::wav::MappedWav;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// Zeroes the `len`-byte field at `offset` of the canonical 44-byte header hound writes.
+fn zero(path: &Path, offset: usize, len: usize) {
+    let mut bytes = std::fs::read(path).unwrap();
+    bytes[offset..offset + len].fill(0);
+    std::fs::write(path, bytes).unwrap();
+}
+
+#[test]
+fn maps_16_bit_pcm() {
+    let file = write_wav("mapped", spec(2), &[1, -2, 3, -4]);
+    let mapped = MappedWav::open(file.path()).unwrap();
+    assert_eq!(mapped.spec().channels, 2);
+    assert_eq!(mapped.samples(), [1, -2, 3, -4]);
+}
+
+#[test]
+fn rejects_zero_channels_sample_rate_and_block_align() {
+    // Channel count (u16) at offset 22, sample rate (u32) at 24, block alignment (u16) at 32
+    for (name, offset, len) in [
+        ("no_channels", 22, 2),
+        ("no_sample_rate", 24, 4),
+        ("no_block_align", 32, 2),
+    ] {
+        let file = write_wav(name, spec(2), &[1, 2, 3, 4]);
+        zero(file.path(), offset, len);
+        let err = MappedWav::open(file.path()).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}